@@ -0,0 +1,155 @@
+use super::interval::Interval;
+use super::ray::Ray;
+use super::Point;
+
+const MIN_SIZE: f64 = 0.0001;
+
+/// Axis-aligned bounding box, stored as one interval per axis.
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    x: Interval,
+    y: Interval,
+    z: Interval,
+}
+
+impl Aabb {
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        Aabb { x, y, z }
+    }
+
+    /// Box spanning the two points, which can be any two opposite corners.
+    ///
+    /// Axes thinner than `MIN_SIZE` are padded, so flat primitives (triangles, quads, ...)
+    /// still get a box the slab test can hit.
+    pub fn from_points(a: Point, b: Point) -> Self {
+        let span = |min: f64, max: f64| {
+            let interval = Interval::new(min, max);
+            if interval.size() < MIN_SIZE {
+                interval.expand(MIN_SIZE)
+            } else {
+                interval
+            }
+        };
+        Aabb {
+            x: span(f64::min(*a.x(), *b.x()), f64::max(*a.x(), *b.x())),
+            y: span(f64::min(*a.y(), *b.y()), f64::max(*a.y(), *b.y())),
+            z: span(f64::min(*a.z(), *b.z()), f64::max(*a.z(), *b.z())),
+        }
+    }
+
+    /// Smallest box containing both `a` and `b`.
+    pub fn enclosing(a: &Aabb, b: &Aabb) -> Self {
+        Aabb {
+            x: Interval::enclosing(&a.x, &b.x),
+            y: Interval::enclosing(&a.y, &b.y),
+            z: Interval::enclosing(&a.z, &b.z),
+        }
+    }
+
+    #[inline(always)]
+    pub const fn empty() -> Self {
+        Aabb {
+            x: Interval::empty(),
+            y: Interval::empty(),
+            z: Interval::empty(),
+        }
+    }
+
+    /// Box containing all of space, for unbounded objects such as infinite planes.
+    #[inline(always)]
+    pub const fn universe() -> Self {
+        Aabb {
+            x: Interval::universe(),
+            y: Interval::universe(),
+            z: Interval::universe(),
+        }
+    }
+
+    /// False if the box is unbounded along any axis.
+    pub fn is_finite(&self) -> bool {
+        [&self.x, &self.y, &self.z]
+            .iter()
+            .all(|axis| axis.min().is_finite() && axis.max().is_finite())
+    }
+
+    /// The 8 corners of the box.
+    pub fn corners(&self) -> [Point; 8] {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|n| {
+            let pick = |interval: &Interval, bit: usize| {
+                if n & bit == 0 {
+                    interval.min()
+                } else {
+                    interval.max()
+                }
+            };
+            Point::new(pick(&self.x, 1), pick(&self.y, 2), pick(&self.z, 4))
+        })
+    }
+
+    #[inline(always)]
+    pub fn axis(&self, n: usize) -> &Interval {
+        match n {
+            1 => &self.y,
+            2 => &self.z,
+            _ => &self.x,
+        }
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() {
+                0
+            } else {
+                2
+            }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn centroid(&self) -> Point {
+        Point::new(
+            (self.x.min() + self.x.max()) * 0.5,
+            (self.y.min() + self.y.max()) * 0.5,
+            (self.z.min() + self.z.max()) * 0.5,
+        )
+    }
+
+    /// Surface area of the box, zero for an empty box. Used by the SAH cost in the BVH builder.
+    pub fn surface_area(&self) -> f64 {
+        let dx = self.x.size();
+        let dy = self.y.size();
+        let dz = self.z.size();
+        if dx < 0.0 || dy < 0.0 || dz < 0.0 {
+            return 0.0;
+        }
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    /// Slab test: true if the ray enters the box anywhere inside `interval`.
+    pub fn hit(&self, ray: &Ray, interval: Interval) -> bool {
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let mut t_min = interval.min();
+        let mut t_max = interval.max();
+
+        for axis in 0..3 {
+            let bounds = self.axis(axis);
+            let inv_d = 1.0 / direction[axis];
+
+            let t0 = (bounds.min() - origin[axis]) * inv_d;
+            let t1 = (bounds.max() - origin[axis]) * inv_d;
+            let (t0, t1) = if inv_d < 0.0 { (t1, t0) } else { (t0, t1) };
+
+            t_min = f64::max(t0, t_min);
+            t_max = f64::min(t1, t_max);
+
+            if t_max <= t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
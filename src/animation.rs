@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hittable::{HitResult, Hittable};
+use super::interval::Interval;
+use super::ray::Ray;
+use super::utilities::deg_to_rad;
+use super::vec3::Vec3;
+
+/// Time steps the motion is sampled at to bound it.
+const BOUNDING_STEPS: usize = 16;
+
+/// Placement of an object at one instant: a rotation around the axis of the
+/// `AnimatedTransform`, followed by a translation.
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe {
+    translation: Vec3,
+    rotation: f64,
+}
+
+impl Keyframe {
+    /// `rotation` is in degrees.
+    pub fn new(translation: Vec3, rotation: f64) -> Self {
+        Keyframe {
+            translation,
+            rotation: deg_to_rad(rotation),
+        }
+    }
+
+    pub fn identity() -> Self {
+        Keyframe::new(Vec3::zero(), 0.0)
+    }
+}
+
+/// Moves any hittable between two keyframes, `start` at time 0 and `end` at time 1, rotating
+/// it around `axis` (through its own origin) and translating it linearly in between.
+pub struct AnimatedTransform {
+    object: Arc<dyn Hittable>,
+    axis: Vec3,
+    start: Keyframe,
+    end: Keyframe,
+    bbox: Aabb,
+}
+
+impl AnimatedTransform {
+    pub fn new(object: Arc<dyn Hittable>, axis: Vec3, start: Keyframe, end: Keyframe) -> Self {
+        let mut transform = AnimatedTransform {
+            object,
+            axis: axis.normalized(),
+            start,
+            end,
+            bbox: Aabb::empty(),
+        };
+        transform.bbox = transform.motion_bounds();
+        transform
+    }
+
+    /// Translation and rotation angle at `time`.
+    fn at(&self, time: f64) -> (Vec3, f64) {
+        let translation = self.start.translation + time * (self.end.translation - self.start.translation);
+        let rotation = self.start.rotation + time * (self.end.rotation - self.start.rotation);
+        (translation, rotation)
+    }
+
+    /// Box swept by the object over the time 0..1.
+    ///
+    /// The corners of the object's box are placed at regular time steps; between two steps
+    /// a corner drifts from the straight line by at most the sagitta of its rotation arc, so
+    /// the result is padded by that much.
+    fn motion_bounds(&self) -> Aabb {
+        if !self.object.bounding_box().is_finite() {
+            return Aabb::universe();
+        }
+        let corners = self.object.bounding_box().corners();
+        let mut bbox = Aabb::empty();
+        for step in 0..=BOUNDING_STEPS {
+            let (translation, angle) = self.at(step as f64 / BOUNDING_STEPS as f64);
+            for corner in &corners {
+                let p = rotate(corner, &self.axis, angle) + translation;
+                bbox = Aabb::enclosing(&bbox, &Aabb::from_points(p, p));
+            }
+        }
+
+        let radius = corners.iter().map(Vec3::len).fold(0.0, f64::max);
+        let step_angle = (self.end.rotation - self.start.rotation).abs() / BOUNDING_STEPS as f64;
+        let sagitta = radius * (1.0 - f64::cos(step_angle * 0.5));
+        Aabb::new(
+            bbox.axis(0).expand(2.0 * sagitta),
+            bbox.axis(1).expand(2.0 * sagitta),
+            bbox.axis(2).expand(2.0 * sagitta),
+        )
+    }
+}
+
+impl Hittable for AnimatedTransform {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitResult> {
+        let (translation, angle) = self.at(ray.time());
+
+        // intersect in object space, then move the hit back
+        let origin = rotate(&(ray.origin() - translation), &self.axis, -angle);
+        let direction = rotate(&ray.direction(), &self.axis, -angle);
+        let local_ray = Ray::with_time(origin, direction, ray.time());
+
+        let hit = self.object.hit(&local_ray, interval)?;
+        let location = rotate(hit.location(), &self.axis, angle) + translation;
+        let normal = rotate(hit.normal(), &self.axis, angle);
+        Some(hit.transform(location, normal))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+/// Rotates `v` by `angle` radians around the unit vector `axis` (Rodrigues' formula).
+fn rotate(v: &Vec3, axis: &Vec3, angle: f64) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    cos * *v + sin * Vec3::cross(axis, v) + (1.0 - cos) * Vec3::dot(axis, v) * *axis
+}
@@ -0,0 +1,327 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use exr::prelude::{AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, WritableImage};
+use image::error::{EncodingError, ImageFormatHint};
+use image::{ImageError, ImageFormat, ImageResult, Rgb32FImage, RgbImage};
+
+use super::color::Color;
+use super::hittable::HitResult;
+use super::ray::Ray;
+use super::vec3::Vec3;
+use super::Point;
+
+/// Arbitrary output variable: a property of the first surface seen through every pixel, saved
+/// next to the image for compositing and denoising.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    /// Outward surface normal, -1..1 per axis.
+    Normal,
+    /// Base color of the material, without lighting.
+    Albedo,
+    /// Distance from the camera, infinite where nothing was hit.
+    Depth,
+    /// World space position.
+    Position,
+    /// Number of the scene object, 0 where nothing was hit.
+    ObjectId,
+}
+
+impl Aov {
+    pub const NAMES: [&'static str; 5] = ["normal", "albedo", "depth", "position", "object-id"];
+    pub const ALL: [Aov; 5] = [Aov::Normal, Aov::Albedo, Aov::Depth, Aov::Position, Aov::ObjectId];
+
+    /// Names of the channels of the AOV in EXR files, after the `<aov>.` prefix.
+    pub fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::Albedo => &["R", "G", "B"],
+            Aov::Depth => &["Z"],
+            Aov::ObjectId => &["id"],
+        }
+    }
+}
+
+impl FromStr for Aov {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(Aov::Normal),
+            "albedo" => Ok(Aov::Albedo),
+            "depth" => Ok(Aov::Depth),
+            "position" => Ok(Aov::Position),
+            "object-id" => Ok(Aov::ObjectId),
+            _ => Err(format!("unknown AOV `{}`, expected one of: {}", s, Aov::NAMES.join(", "))),
+        }
+    }
+}
+
+impl Display for Aov {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::ObjectId => "object-id",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Every AOV of one pixel, averaged over the camera rays shot through it.
+#[derive(Debug, Clone, Copy)]
+pub struct AovPixel {
+    normal: Vec3,
+    albedo: Color,
+    depth: f64,
+    position: Point,
+    object_id: u32,
+}
+
+impl AovPixel {
+    /// Combines the first hits of the camera rays of a pixel. The normal and albedo are
+    /// averaged over all the rays, so edges get antialiased like the image; the depth and
+    /// position only over the rays that hit something, and the object ID is the one seen by
+    /// most rays.
+    pub fn from_hits<I: IntoIterator<Item = (Ray, Option<HitResult>)>>(hits: I) -> Self {
+        let mut rays = 0;
+        let mut hit_count = 0;
+        let (mut normal, mut albedo, mut position) = (Vec3::zero(), Color::black(), Point::zero());
+        let mut depth = 0.0;
+        let mut ids: Vec<(u32, usize)> = Vec::new();
+
+        for (ray, hit) in hits {
+            rays += 1;
+            let id = hit.as_ref().map_or(0, |hit| hit.object_id());
+            match ids.iter_mut().find(|(other, _)| *other == id) {
+                Some((_, count)) => *count += 1,
+                None => ids.push((id, 1)),
+            }
+
+            if let Some(hit) = hit {
+                hit_count += 1;
+                normal += hit.outward_normal();
+                albedo += hit.material().albedo(&hit);
+                depth += hit.t() * ray.direction().len();
+                position += *hit.location();
+            }
+        }
+
+        let object_id = ids
+            .iter()
+            .max_by_key(|(_, count)| *count)
+            .map_or(0, |(id, _)| *id);
+        let rays = rays.max(1) as f64;
+        match hit_count {
+            0 => AovPixel {
+                normal: Vec3::zero(),
+                albedo: Color::black(),
+                depth: f64::INFINITY,
+                position: Point::zero(),
+                object_id,
+            },
+            hits => AovPixel {
+                normal: normal / rays,
+                albedo: albedo / rays,
+                depth: depth / hits as f64,
+                position: position / hits as f64,
+                object_id,
+            },
+        }
+    }
+
+    fn values(&self, aov: Aov) -> impl Iterator<Item = f32> {
+        let values = match aov {
+            Aov::Normal => [*self.normal.x(), *self.normal.y(), *self.normal.z()],
+            Aov::Albedo => [*self.albedo.r(), *self.albedo.g(), *self.albedo.b()],
+            Aov::Depth => [self.depth, 0.0, 0.0],
+            Aov::Position => [*self.position.x(), *self.position.y(), *self.position.z()],
+            Aov::ObjectId => [self.object_id as f64, 0.0, 0.0],
+        };
+        values.into_iter().take(aov.channels().len()).map(|value| value as f32)
+    }
+}
+
+/// Images of the requested AOVs, row by row, as many `f32` per pixel as the AOV has channels.
+#[derive(Debug, Clone)]
+pub struct Aovs {
+    width: usize,
+    height: usize,
+    layers: Vec<(Aov, Vec<f32>)>,
+}
+
+impl Aovs {
+    /// Keeps the `aovs` of `pixels`, given row by row.
+    pub fn new(width: usize, height: usize, aovs: &[Aov], pixels: &[AovPixel]) -> Self {
+        let layers = aovs
+            .iter()
+            .map(|&aov| (aov, pixels.iter().flat_map(|pixel| pixel.values(aov)).collect()))
+            .collect();
+        Aovs { width, height, layers }
+    }
+
+    #[inline(always)]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline(always)]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Values of `aov`, `None` if it wasn't rendered.
+    pub fn layer(&self, aov: Aov) -> Option<&[f32]> {
+        self.layers
+            .iter()
+            .find(|(other, _)| *other == aov)
+            .map(|(_, values)| values.as_slice())
+    }
+
+    /// The AOVs of `aovs` that were rendered, in that order.
+    pub fn select(&self, aovs: &[Aov]) -> Aovs {
+        let layers = aovs
+            .iter()
+            .filter_map(|&aov| self.layer(aov).map(|values| (aov, values.to_vec())))
+            .collect();
+        Aovs {
+            width: self.width,
+            height: self.height,
+            layers,
+        }
+    }
+
+    /// Saves every AOV to a file of its own next to `path`, named after the AOV (`image.png`
+    /// gives `image.normal.png`, ...). High dynamic range formats keep the raw values, others
+    /// get them squeezed into a viewable 8-bit image.
+    pub fn save_separate<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        let path = path.as_ref();
+        let (width, height) = (self.width as u32, self.height as u32);
+        let high_dynamic_range = matches!(
+            ImageFormat::from_path(path)?,
+            ImageFormat::OpenExr | ImageFormat::Hdr
+        );
+
+        for (aov, values) in &self.layers {
+            let aov_path = layer_path(path, *aov);
+            if high_dynamic_range {
+                // single channel AOVs are repeated in every color channel
+                let rgb = values
+                    .chunks_exact(aov.channels().len())
+                    .flat_map(|pixel| [0, 1, 2].map(|c| pixel[c.min(pixel.len() - 1)]))
+                    .collect();
+                Rgb32FImage::from_raw(width, height, rgb)
+                    .expect("AOV size matches its dimensions")
+                    .save(aov_path)?;
+            } else {
+                RgbImage::from_raw(width, height, self.to_rgb8(*aov, values))
+                    .expect("AOV size matches its dimensions")
+                    .save(aov_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves `radiance` (RGB `f32` values, see `Framebuffer::radiance`) and every AOV as the
+    /// channels of one OpenEXR file. The image keeps the plain `R`, `G` and `B` channels that
+    /// viewers show by default, AOV channels are prefixed with the AOV name (`normal.X`, ...).
+    pub fn save_exr<P: AsRef<Path>>(&self, path: P, radiance: &[f32]) -> ImageResult<()> {
+        let channel = |name: String, values: Vec<f32>| AnyChannel::new(name.as_str(), FlatSamples::F32(values));
+
+        let mut channels = Vec::new();
+        for (c, name) in ["R", "G", "B"].iter().enumerate() {
+            let values = radiance.iter().skip(c).step_by(3).copied().collect();
+            channels.push(channel(name.to_string(), values));
+        }
+        for (aov, values) in &self.layers {
+            let names = aov.channels();
+            for (c, name) in names.iter().enumerate() {
+                let values = values.iter().skip(c).step_by(names.len()).copied().collect();
+                channels.push(channel(format!("{}.{}", aov, name), values));
+            }
+        }
+
+        let layer = Layer::new(
+            (self.width, self.height),
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(channels.into()),
+        );
+        Image::from_layer(layer)
+            .write()
+            .to_file(path)
+            .map_err(|err| {
+                ImageError::Encoding(EncodingError::new(ImageFormatHint::Exact(ImageFormat::OpenExr), err))
+            })
+    }
+
+    /// Viewable 8-bit version of an AOV: normals as the usual normal map colors, albedo in
+    /// sRGB, depth and position scaled to the range they cover, object IDs in random colors.
+    fn to_rgb8(&self, aov: Aov, values: &[f32]) -> Vec<u8> {
+        let to_u8 = |value: f64| (255.0 * value.clamp(0.0, 1.0)).round() as u8;
+        match aov {
+            Aov::Normal => values.iter().map(|&n| to_u8(0.5 * (n as f64 + 1.0))).collect(),
+            Aov::Albedo => values
+                .chunks_exact(3)
+                .flat_map(|pixel| {
+                    let color = Color::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64);
+                    let encoded = Color::linear_to_srgb(&color);
+                    [*encoded.r(), *encoded.g(), *encoded.b()].map(to_u8)
+                })
+                .collect(),
+            Aov::Depth => {
+                // nearest is black, the farthest surface and the background white
+                let far = values.iter().copied().filter(|d| d.is_finite()).fold(0.0, f32::max);
+                values
+                    .iter()
+                    .flat_map(|&depth| {
+                        let gray = if depth.is_finite() && far > 0.0 { depth / far } else { 1.0 };
+                        [to_u8(gray as f64); 3]
+                    })
+                    .collect()
+            }
+            Aov::Position => {
+                let mut min = [f32::INFINITY; 3];
+                let mut max = [f32::NEG_INFINITY; 3];
+                for pixel in values.chunks_exact(3) {
+                    for c in 0..3 {
+                        min[c] = min[c].min(pixel[c]);
+                        max[c] = max[c].max(pixel[c]);
+                    }
+                }
+                values
+                    .chunks_exact(3)
+                    .flat_map(|pixel| {
+                        [0, 1, 2].map(|c| {
+                            let range = max[c] - min[c];
+                            to_u8(if range > 0.0 { ((pixel[c] - min[c]) / range) as f64 } else { 0.0 })
+                        })
+                    })
+                    .collect()
+            }
+            Aov::ObjectId => values
+                .iter()
+                .flat_map(|&id| match id as u64 {
+                    0 => [0; 3],
+                    id => {
+                        let color = Color::from_id(id);
+                        [*color.r(), *color.g(), *color.b()].map(to_u8)
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// `path` with the name of a layer (an AOV, `raw`, ...) inserted before the extension.
+pub fn layer_path<L: Display>(path: &Path, layer: L) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, layer, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, layer),
+    };
+    path.with_file_name(name)
+}
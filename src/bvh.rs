@@ -0,0 +1,252 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hittable::{HitResult, Hittable};
+use super::hittable_list::HittableList;
+use super::interval::Interval;
+use super::ray::Ray;
+use super::Point;
+
+/// Number of buckets used when evaluating SAH split candidates.
+const SAH_BUCKETS: usize = 12;
+
+/// Bounding volume hierarchy node.
+///
+/// The tree is built top-down with a binned surface area heuristic along the longest axis of
+/// the primitive centroids, falling back to a median split whenever the heuristic can't
+/// separate the primitives (e.g. all centroids in the same bucket).
+pub struct BvhNode {
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    bbox: Aabb,
+}
+
+struct Primitive {
+    object: Arc<dyn Hittable>,
+    bbox: Aabb,
+    centroid: Point,
+}
+
+impl BvhNode {
+    pub fn new(objects: Vec<Arc<dyn Hittable>>) -> Self {
+        // unbounded objects (infinite planes) have no centroid to sort by, they are kept
+        // next to the tree and tested by every ray
+        let (objects, unbounded): (Vec<_>, Vec<_>) =
+            objects.into_iter().partition(|object| object.bounding_box().is_finite());
+        if !unbounded.is_empty() {
+            let mut list = HittableList::new();
+            for object in unbounded {
+                list.add(object);
+            }
+            let tree = BvhNode::new(objects);
+            return BvhNode {
+                bbox: Aabb::enclosing(&list.bounding_box(), &tree.bbox),
+                left: Arc::new(list),
+                right: Arc::new(tree),
+            };
+        }
+
+        let mut primitives: Vec<Primitive> = objects
+            .into_iter()
+            .map(|object| {
+                let bbox = object.bounding_box();
+                Primitive {
+                    object,
+                    bbox,
+                    centroid: bbox.centroid(),
+                }
+            })
+            .collect();
+
+        match primitives.len() {
+            0 => {
+                let empty: Arc<dyn Hittable> = Arc::new(HittableList::new());
+                BvhNode {
+                    left: Arc::clone(&empty),
+                    right: empty,
+                    bbox: Aabb::empty(),
+                }
+            }
+            1 => {
+                let primitive = primitives.pop().unwrap();
+                BvhNode {
+                    left: Arc::clone(&primitive.object),
+                    right: primitive.object,
+                    bbox: primitive.bbox,
+                }
+            }
+            _ => BvhNode::split(primitives),
+        }
+    }
+
+    pub fn from_list(list: &HittableList) -> Self {
+        BvhNode::new(list.objects().to_vec())
+    }
+
+    fn build(mut primitives: Vec<Primitive>) -> Arc<dyn Hittable> {
+        if primitives.len() == 1 {
+            return primitives.pop().unwrap().object;
+        }
+        Arc::new(BvhNode::split(primitives))
+    }
+
+    fn split(mut primitives: Vec<Primitive>) -> Self {
+        let bbox = primitives
+            .iter()
+            .fold(Aabb::empty(), |acc, p| Aabb::enclosing(&acc, &p.bbox));
+
+        let right = if primitives.len() == 2 {
+            primitives.split_off(1)
+        } else {
+            let mid = BvhNode::partition(&mut primitives);
+            primitives.split_off(mid)
+        };
+
+        BvhNode {
+            left: BvhNode::build(primitives),
+            right: BvhNode::build(right),
+            bbox,
+        }
+    }
+
+    /// Reorders `primitives` so that `[..mid]` and `[mid..]` are the two children, returning `mid`.
+    fn partition(primitives: &mut [Primitive]) -> usize {
+        let centroid_bounds = primitives.iter().fold(Aabb::empty(), |acc, p| {
+            let c = &p.centroid;
+            let point = Aabb::new(
+                Interval::new(*c.x(), *c.x()),
+                Interval::new(*c.y(), *c.y()),
+                Interval::new(*c.z(), *c.z()),
+            );
+            Aabb::enclosing(&acc, &point)
+        });
+        let axis = centroid_bounds.longest_axis();
+        let extent = centroid_bounds.axis(axis);
+
+        if extent.size() > 0.0 {
+            if let Some(mid) = BvhNode::sah_partition(primitives, axis, extent) {
+                return mid;
+            }
+        }
+
+        let mid = primitives.len() / 2;
+        primitives.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+        mid
+    }
+
+    fn sah_partition(primitives: &mut [Primitive], axis: usize, extent: &Interval) -> Option<usize> {
+        let bucket_of = |p: &Primitive| {
+            let offset = (p.centroid[axis] - extent.min()) / extent.size();
+            usize::min((offset * SAH_BUCKETS as f64) as usize, SAH_BUCKETS - 1)
+        };
+
+        let mut counts = [0usize; SAH_BUCKETS];
+        let mut bounds = [Aabb::empty(); SAH_BUCKETS];
+        for p in primitives.iter() {
+            let b = bucket_of(p);
+            counts[b] += 1;
+            bounds[b] = Aabb::enclosing(&bounds[b], &p.bbox);
+        }
+
+        // sweep from the right to get the cost of every "everything right of bucket i" set
+        let mut right_area = [0.0; SAH_BUCKETS];
+        let mut right_count = [0usize; SAH_BUCKETS];
+        let mut acc_bbox = Aabb::empty();
+        let mut acc_count = 0;
+        for i in (1..SAH_BUCKETS).rev() {
+            acc_bbox = Aabb::enclosing(&acc_bbox, &bounds[i]);
+            acc_count += counts[i];
+            right_area[i] = acc_bbox.surface_area();
+            right_count[i] = acc_count;
+        }
+
+        let mut best: Option<(usize, f64)> = None;
+        let mut acc_bbox = Aabb::empty();
+        let mut acc_count = 0;
+        for i in 0..SAH_BUCKETS - 1 {
+            acc_bbox = Aabb::enclosing(&acc_bbox, &bounds[i]);
+            acc_count += counts[i];
+            if acc_count == 0 || right_count[i + 1] == 0 {
+                continue;
+            }
+            let cost = acc_bbox.surface_area() * acc_count as f64
+                + right_area[i + 1] * right_count[i + 1] as f64;
+            if best.is_none_or(|(_, best_cost)| cost < best_cost) {
+                best = Some((i, cost));
+            }
+        }
+
+        let (split_bucket, _) = best?;
+        primitives.sort_unstable_by_key(|p| bucket_of(p) > split_bucket);
+        Some(primitives.iter().take_while(|p| bucket_of(p) <= split_bucket).count())
+    }
+}
+
+impl From<HittableList> for BvhNode {
+    fn from(list: HittableList) -> Self {
+        BvhNode::from_list(&list)
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitResult> {
+        if !self.bbox.hit(ray, interval) {
+            return None;
+        }
+
+        let hit_left = self.left.hit(ray, interval);
+        if Arc::ptr_eq(&self.left, &self.right) {
+            return hit_left;
+        }
+
+        let right_max = hit_left.as_ref().map_or(interval.max(), |hit| hit.t());
+        let hit_right = self.right.hit(ray, Interval::new(interval.min(), right_max));
+
+        hit_right.or(hit_left)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::utilities::{rand_range, seed};
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn closest_hit_matches_linear_scan() {
+        seed(7);
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut list = HittableList::new();
+        for _ in 0..64 {
+            let center = Point::rand_range(-10.0, 10.0);
+            list.add(Arc::new(Sphere::new(center, rand_range(0.2, 1.5), material.clone())));
+        }
+        let list = list.numbered();
+        let bvh = BvhNode::from_list(&list);
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let ray = Ray::new(Point::rand_range(-15.0, 15.0), Vec3::rand_unit());
+            let interval = Interval::new(0.001, f64::INFINITY);
+            let expected = list.hit(&ray, interval);
+            let actual = bvh.hit(&ray, interval);
+            match (&expected, &actual) {
+                (None, None) => (),
+                (Some(expected), Some(actual)) => {
+                    hits += 1;
+                    assert_eq!(expected.object_id(), actual.object_id());
+                    assert_eq!(expected.t(), actual.t());
+                }
+                _ => panic!("BVH hit {:?}, linear scan hit {:?}", actual.is_some(), expected.is_some()),
+            }
+        }
+        assert!(hits > 100, "only {} rays hit something", hits);
+    }
+}
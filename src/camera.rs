@@ -1,35 +1,59 @@
-use std::sync::Mutex;
-use crate::interval::Interval;
-use crate::utilities::deg_to_rad;
-
+use std::fmt::Debug;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use crate::utilities::{self, deg_to_rad};
+
+use super::aov::{Aov, AovPixel, Aovs};
 use super::color::Color;
+use super::environment::{Environment, GradientEnvironment};
+use super::framebuffer::Framebuffer;
 use super::hittable::Hittable;
+use super::hittable_list::HittableList;
+use super::integrator::{Integrator, PathTracer, RenderContext};
+use super::interval::Interval;
 use super::ray::Ray;
+use super::sampling::AdaptiveSampling;
 use super::vec3::Vec3;
 use super::Point;
 
 use rayon::prelude::*;
 
-#[derive(Debug)]
+/// Side, in pixels, of the square tiles scheduled by `render_parallel`.
+const TILE_SIZE: usize = 16;
+
+const SQUARES: usize = Framebuffer::SQUARES;
+const SAMPLES: usize = Framebuffer::SAMPLES;
+const STRIDE: usize = Framebuffer::STRIDE;
+
+/// Most camera rays averaged by every pixel of the AOVs, first hits converge much faster than
+/// the image.
+const AOV_SAMPLES: u32 = 16;
+
 pub struct Camera {
     center: Point,
     viewport: Viewport,
+    aspect_ratio: f64,
     samples: u32,
     max_depth: u32,
     v_fov: f64,
-    lookfrom: Point,
     lookat: Point,
-    vup: Vec3,
-    u: Vec3,
-    v: Vec3,
-    w: Vec3,
-    defocus_angle: f64,
     focus_dist: f64,
+    defocus_angle: f64,
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
+    background: Arc<dyn Environment>,
+    lights: Arc<HittableList>,
+    integrator: Arc<dyn Integrator>,
+    seed: u64,
+    adaptive_sampling: Option<AdaptiveSampling>,
+    shutter_open: f64,
+    shutter_close: f64,
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         aspect_ratio: f64,
         image_width: usize,
@@ -40,6 +64,35 @@ impl Camera {
         lookat: Point,
         focus_dist: f64,
         defocus_angle: f64,
+    ) -> Self {
+        let image_height = usize::max((image_width as f64 / aspect_ratio) as usize, 1);
+        let mut camera = Camera::sized(
+            image_width,
+            image_height,
+            samples,
+            max_depth,
+            v_fov,
+            lookfrom,
+            lookat,
+            focus_dist,
+            defocus_angle,
+        );
+        camera.aspect_ratio = aspect_ratio;
+        camera
+    }
+
+    /// Camera rendering an image of exactly `image_width` by `image_height` pixels.
+    #[allow(clippy::too_many_arguments)]
+    fn sized(
+        image_width: usize,
+        image_height: usize,
+        samples: u32,
+        max_depth: u32,
+        v_fov: f64,
+        lookfrom: Point,
+        lookat: Point,
+        focus_dist: f64,
+        defocus_angle: f64,
     ) -> Self {
         let vup = Vec3::up();
         let center = lookfrom;
@@ -50,7 +103,7 @@ impl Camera {
         let u = Vec3::cross(&vup, &w).normalized();
         let v = Vec3::cross(&w, &u);
 
-        let viewport = Viewport::new(aspect_ratio, h, image_width, focus_dist, center, u, v, w);
+        let viewport = Viewport::new(image_width, image_height, h, focus_dist, center, u, v, w);
 
         let defocus_radius = focus_dist * f64::tan(deg_to_rad(defocus_angle * 0.5));
         let defocus_disk_u = u * defocus_radius;
@@ -59,34 +112,114 @@ impl Camera {
         Camera {
             center,
             viewport,
+            aspect_ratio: image_width as f64 / image_height as f64,
             samples,
             max_depth,
             v_fov,
             lookat,
-            lookfrom,
-            u,
-            v,
-            w,
-            vup,
             focus_dist,
             defocus_angle,
             defocus_disk_u,
             defocus_disk_v,
+            background: Arc::new(GradientEnvironment::sky()),
+            lights: Arc::new(HittableList::new()),
+            integrator: Arc::new(PathTracer::default()),
+            seed: 0,
+            adaptive_sampling: None,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
         }
     }
 
-    pub fn default() -> Camera {
-        Camera::new(
-            16.0 / 9.0,
-            400,
-            10,
-            10,
-            90.0,
-            Point::zero(),
-            Point::new(0.0, 0.0, -1.0),
-            10.0,
-            0.0,
-        )
+    /// Same camera rendering an image of `image_width` by `image_height` pixels.
+    pub fn with_image_size(&self, image_width: usize, image_height: usize) -> Camera {
+        let mut camera = Camera::sized(
+            usize::max(image_width, 1),
+            usize::max(image_height, 1),
+            self.samples,
+            self.max_depth,
+            self.v_fov,
+            self.center,
+            self.lookat,
+            self.focus_dist,
+            self.defocus_angle,
+        );
+        camera.background = Arc::clone(&self.background);
+        camera.lights = Arc::clone(&self.lights);
+        camera.integrator = Arc::clone(&self.integrator);
+        camera.seed = self.seed;
+        camera.adaptive_sampling = self.adaptive_sampling;
+        camera.shutter_open = self.shutter_open;
+        camera.shutter_close = self.shutter_close;
+        camera
+    }
+
+    #[inline(always)]
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    #[inline(always)]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_samples(&mut self, samples: u32) {
+        self.samples = samples;
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: u32) {
+        self.max_depth = max_depth;
+    }
+
+    /// How the color of every sample is computed, the path tracer by default.
+    pub fn set_integrator(&mut self, integrator: Arc<dyn Integrator>) {
+        self.integrator = integrator;
+    }
+
+    /// Seed every pixel sample's random stream is derived from. Renders with the same seed are
+    /// identical, whatever the number of threads.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Stops sampling pixels that have converged, `samples` becomes the most a pixel can get.
+    pub fn set_adaptive_sampling(&mut self, adaptive_sampling: Option<AdaptiveSampling>) {
+        self.adaptive_sampling = adaptive_sampling;
+    }
+
+    #[inline(always)]
+    pub fn adaptive_sampling(&self) -> Option<AdaptiveSampling> {
+        self.adaptive_sampling
+    }
+
+    /// Time interval the shutter stays open, every ray gets a random time within it. Objects
+    /// moving during the interval are motion blurred; an empty interval freezes the scene at
+    /// `open`.
+    pub fn set_shutter(&mut self, open: f64, close: f64) {
+        self.shutter_open = open;
+        self.shutter_close = close;
+    }
+
+    #[inline(always)]
+    pub fn shutter(&self) -> (f64, f64) {
+        (self.shutter_open, self.shutter_close)
+    }
+
+    /// What the rays escaping the scene see, the sky gradient by default.
+    pub fn set_background(&mut self, background: Arc<dyn Environment>) {
+        self.background = background;
+    }
+
+    /// Objects sampled directly as light sources. They must also be part of the world, but only
+    /// the ones that can be sampled (spheres, quads, disks, triangles and their transforms) help.
+    pub fn set_lights(&mut self, lights: Arc<HittableList>) {
+        self.lights = lights;
+    }
+
+    #[inline(always)]
+    pub fn aspect_ratio(&self) -> f64 {
+        self.aspect_ratio
     }
 
     #[inline(always)]
@@ -104,99 +237,155 @@ impl Camera {
         self.viewport.image_width
     }
 
-    pub fn render(&self, world: &dyn Hittable, image_data: &mut Vec<u8>) {
+    /// Adds up to `pass_samples` samples to every pixel of the accumulation buffer
+    /// `image_data` (see `Framebuffer`), stopping at `samples` per pixel or, with adaptive
+    /// sampling, once the pixel has converged. Returns how many pixels still need samples.
+    ///
+    /// Each pixel continues from the samples it already holds, so rendering in several passes
+    /// gives the same image as rendering everything at once.
+    pub fn render(&self, world: &dyn Hittable, image_data: &mut [f64], pass_samples: u32) -> usize {
+        let mut remaining = 0;
         for j in 0..self.height() {
-            println!("\rScanlines remaining: {} ", self.height() - j);
-
-            for i in 0..self.width() {
-                let pixel_color = (0..self.samples)
-                    .map(|_| {
-                        let ray = self.get_ray(i, j);
-                        ray.color(world, self.max_depth)
-                    })
-                    .reduce(|acc, color| (acc + color))
-                    .unwrap_or(Vec3::black());
-                self.write_pixel(&pixel_color, self.samples, image_data, (i, j));
+            print!("\rScanlines remaining: {} ", self.height() - j);
+            io::stdout().flush().ok();
+
+            let line = &mut image_data[j * self.width() * STRIDE..(j + 1) * self.width() * STRIDE];
+            for (i, pixel) in line.chunks_exact_mut(STRIDE).enumerate() {
+                remaining += self.render_pixel(world, i, j, pass_samples, pixel) as usize;
             }
         }
 
-        println!("\rDone.");
+        println!("\rDone.{:20}", "");
+        remaining
     }
 
-    pub fn render_parallel(&self, world: &(dyn Hittable + Sync + Send), image_data: &mut Vec<u8>) {
-        for j in 0..self.height() {
-            println!("\rScanlines remaining: {} ", self.height() - j);
-            let mutex = Mutex::new(&mut *image_data);
-            (0 .. self.width()).into_par_iter().for_each(|i| {
-                let pixel_color = (0..self.samples)
-                    .into_iter()
-                    .map(|_| {
-                        let ray = self.get_ray(i, j);
-                        ray.color(world, self.max_depth)
-                    }).reduce(|acc, color| (acc + color)).unwrap_or(Color::black());
-                self.write_pixel_mutex(&pixel_color, self.samples, &mutex, (i, j));
-            });
-        }
-        println!("\rDone.");
+    /// Same as `render`, on the current rayon thread pool.
+    ///
+    /// The image is split into `TILE_SIZE` square tiles that are scheduled independently, each
+    /// one owning the disjoint row slices of the image it covers, so no locking is needed.
+    pub fn render_parallel(&self, world: &dyn Hittable, image_data: &mut [f64], pass_samples: u32) -> usize {
+        let tiles = self.tiles(image_data);
+        let tile_count = tiles.len();
+        let tiles_x = self.width().div_ceil(TILE_SIZE);
+        let tiles_done = AtomicUsize::new(0);
+
+        let remaining = tiles
+            .into_par_iter()
+            .map(|mut tile| {
+                let mut remaining = 0;
+                for (row, line) in tile.rows.iter_mut().enumerate() {
+                    let j = tile.y + row;
+                    for (column, pixel) in line.chunks_exact_mut(STRIDE).enumerate() {
+                        remaining += self.render_pixel(world, tile.x + column, j, pass_samples, pixel) as usize;
+                    }
+                }
+                // report once per row of tiles, as often as the single threaded scanlines
+                let done = tiles_done.fetch_add(1, Ordering::Relaxed) + 1;
+                if done.is_multiple_of(tiles_x) || done == tile_count {
+                    print!("\rTiles remaining: {} ", tile_count - done);
+                    io::stdout().flush().ok();
+                }
+                remaining
+            })
+            .sum();
+
+        println!("\rDone.{:20}", "");
+        remaining
     }
 
-    fn write_pixel(
-        &self,
-        color: &Color,
-        samples: u32,
-        image_data: &mut Vec<u8>,
-        uv: (usize, usize),
-    ) {
-        let scale = 1.0 / samples as f64;
-
-        let intesity: Interval = Interval::new(0.0, 1.0);
-
-        let color = Color::linear_to_gamma(&Color::new(
-            *color.r() * scale,
-            *color.g() * scale,
-            *color.b() * scale,
-        ));
-
-        let r = 255f64 * intesity.clamp(*color.r());
-        let g = 255f64 * intesity.clamp(*color.g());
-        let b = 255f64 * intesity.clamp(*color.b());
-
-        let index = uv.1 * (self.width() * 3) + uv.0 * 3;
+    /// Splits `image_data` into tiles, walking it row by row and handing every run of
+    /// `TILE_SIZE` pixels to the tile it belongs to.
+    fn tiles<'a>(&self, image_data: &'a mut [f64]) -> Vec<Tile<'a>> {
+        let tiles_x = self.width().div_ceil(TILE_SIZE);
+        let tiles_y = self.height().div_ceil(TILE_SIZE);
+
+        let mut tiles: Vec<Tile> = (0..tiles_x * tiles_y)
+            .map(|n| Tile {
+                x: (n % tiles_x) * TILE_SIZE,
+                y: (n / tiles_x) * TILE_SIZE,
+                rows: Vec::with_capacity(TILE_SIZE),
+            })
+            .collect();
+
+        for (j, line) in image_data.chunks_exact_mut(self.width() * STRIDE).enumerate() {
+            for (tile_column, span) in line.chunks_mut(TILE_SIZE * STRIDE).enumerate() {
+                tiles[(j / TILE_SIZE) * tiles_x + tile_column].rows.push(span);
+            }
+        }
 
-        image_data[index + 0] = r as u8;
-        image_data[index + 1] = g as u8;
-        image_data[index + 2] = b as u8;
+        tiles
     }
 
-    fn write_pixel_mutex(
-        &self,
-        color: &Color,
-        samples: u32,
-        image_data: &Mutex<&mut Vec<u8>>,
-        uv: (usize, usize),
-    ) {
-        let scale = 1.0 / samples as f64;
-
-        let intesity: Interval = Interval::new(0.0, 1.0);
-
-        let color = Color::linear_to_gamma(&Color::new(
-            *color.r() * scale,
-            *color.g() * scale,
-            *color.b() * scale,
-        ));
+    /// Accumulates the next samples of pixel (`i`, `j`) into its `STRIDE` values, returns
+    /// whether it still needs more.
+    fn render_pixel(&self, world: &dyn Hittable, i: usize, j: usize, pass_samples: u32, pixel: &mut [f64]) -> bool {
+        let converged = |pixel: &[f64]| self.adaptive_sampling.is_some_and(|adaptive| adaptive.converged(pixel));
+        if converged(pixel) {
+            return false;
+        }
 
-        let r = 255f64 * intesity.clamp(*color.r());
-        let g = 255f64 * intesity.clamp(*color.g());
-        let b = 255f64 * intesity.clamp(*color.b());
+        let first = pixel[SAMPLES] as u32;
+        let mut last = first.saturating_add(pass_samples);
+        if let Some(adaptive) = &self.adaptive_sampling {
+            last = last.max(adaptive.min_samples());
+        }
+        let last = last.min(self.samples);
+
+        if first < last {
+            let sums = (Color::new(pixel[0], pixel[1], pixel[2]), pixel[SQUARES]);
+            let (color, squares) = self.sample_pixel(world, i, j, first..last, sums);
+            pixel[0] = *color.r();
+            pixel[1] = *color.g();
+            pixel[2] = *color.b();
+            pixel[SQUARES] = squares;
+            pixel[SAMPLES] = last as f64;
+        }
 
-        let index = uv.1 * (self.width() * 3) + uv.0 * 3;
+        last < self.samples && !converged(pixel)
+    }
 
-        let mut image_data = image_data.lock().unwrap();
+    /// `sums` plus the `samples` of pixel (`i`, `j`) and their squared luminance. Sample `n` of a
+    /// pixel always traces the same paths, whatever pass it is rendered in, and is added to the
+    /// sums in the same order so passes don't change the rounding either.
+    fn sample_pixel(&self, world: &dyn Hittable, i: usize, j: usize, samples: Range<u32>, sums: (Color, f64)) -> (Color, f64) {
+        let pixel = (j * self.width() + i) as u64;
+        let context = RenderContext {
+            world,
+            lights: &self.lights,
+            background: self.background.as_ref(),
+            max_depth: self.max_depth,
+        };
+        samples.fold(sums, |(sum, squares), sample| {
+            utilities::seed_sample(self.seed, pixel, sample as u64);
+            let ray = self.get_ray(i, j);
+            let color = self.integrator.color(&ray, &context);
+            (sum + color, squares + color.luminance() * color.luminance())
+        })
+    }
 
-        image_data[index + 0] = r as u8;
-        image_data[index + 1] = g as u8;
-        image_data[index + 2] = b as u8;
+    /// Renders the `aovs` of the first surface seen through every pixel, on the current rayon
+    /// thread pool. Every pixel averages up to `AOV_SAMPLES` camera rays, the same ones as its
+    /// first image samples, so the AOVs line up with the image down to the antialiased edges.
+    pub fn render_aovs(&self, world: &dyn Hittable, aovs: &[Aov]) -> Aovs {
+        let samples = self.samples.clamp(1, AOV_SAMPLES);
+
+        let pixels: Vec<AovPixel> = (0..self.width() * self.height())
+            .into_par_iter()
+            .map(|pixel| {
+                let (i, j) = (pixel % self.width(), pixel / self.width());
+                AovPixel::from_hits((0..samples).map(|sample| {
+                    utilities::seed_sample(self.seed, pixel as u64, sample as u64);
+                    let ray = self.get_ray(i, j);
+                    // interval starts from 0.001 to hack away the shadow acne problem
+                    let hit = world.hit(&ray, Interval::new(0.001, f64::INFINITY));
+                    (ray, hit)
+                }))
+            })
+            .collect();
+
+        Aovs::new(self.width(), self.height(), aovs, &pixels)
     }
+
     fn get_ray(&self, i: usize, j: usize) -> Ray {
         let pixel_center = self.viewport.pixel_00
             + (i as f64 * self.viewport.pixel_delta_u)
@@ -209,12 +398,16 @@ impl Camera {
             self.defocus_disk_sample()
         };
         let ray_direction = sample - ray_origin;
+        let ray_time = if self.shutter_close > self.shutter_open {
+            utilities::rand_range(self.shutter_open, self.shutter_close)
+        } else {
+            self.shutter_open
+        };
 
-        Ray::new(ray_origin, ray_direction)
+        Ray::with_time(ray_origin, ray_direction, ray_time)
     }
 
     fn pixel_sample_square(&self) -> Vec3 {
-        use super::utilities;
         let px = -0.5 + utilities::rand();
         let py = -0.5 + utilities::rand();
 
@@ -227,34 +420,70 @@ impl Camera {
     }
 }
 
+impl Debug for Camera {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Camera")
+            .field("center", &self.center)
+            .field("viewport", &self.viewport)
+            .field("samples", &self.samples)
+            .field("max_depth", &self.max_depth)
+            .field("v_fov", &self.v_fov)
+            .field("lookat", &self.lookat)
+            .field("focus_dist", &self.focus_dist)
+            .field("defocus_angle", &self.defocus_angle)
+            .field("seed", &self.seed)
+            .field("adaptive_sampling", &self.adaptive_sampling)
+            .field("shutter_open", &self.shutter_open)
+            .field("shutter_close", &self.shutter_close)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera::new(
+            16.0 / 9.0,
+            400,
+            10,
+            10,
+            90.0,
+            Point::zero(),
+            Point::new(0.0, 0.0, -1.0),
+            10.0,
+            0.0,
+        )
+    }
+}
+
+/// A rectangle of the image with its top left corner at (`x`, `y`), holding one slice of the
+/// accumulation data per row it covers.
+struct Tile<'a> {
+    x: usize,
+    y: usize,
+    rows: Vec<&'a mut [f64]>,
+}
+
 #[derive(Debug)]
 struct Viewport {
     pub image_width: usize,
     pub image_height: usize,
-    viewport_width: f64,
-    viewport_height: f64,
-    viewport_u: Vec3,
-    viewport_v: Vec3,
     pub pixel_delta_u: Vec3,
     pub pixel_delta_v: Vec3,
     pub pixel_00: Vec3,
 }
 
 impl Viewport {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        aspect_ratio: f64,
-        h: f64,
         image_width: usize,
+        image_height: usize,
+        h: f64,
         focus_dist: f64,
         center: Point,
         u: Vec3,
         v: Vec3,
         w: Vec3,
     ) -> Viewport {
-        let mut image_height = (image_width as f64 / aspect_ratio) as usize;
-        if image_height < 1 {
-            image_height = 1;
-        }
         let viewport_height = 2.0 * h * focus_dist;
         let viewport_width = viewport_height * (image_width as f64 / image_height as f64);
         let viewport_u = viewport_width * u;
@@ -270,10 +499,6 @@ impl Viewport {
         Viewport {
             image_width,
             image_height,
-            viewport_width,
-            viewport_height,
-            viewport_u,
-            viewport_v,
             pixel_delta_u,
             pixel_delta_v,
             pixel_00,
@@ -0,0 +1,124 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Error, ErrorKind, Write};
+use std::path::Path;
+
+use super::framebuffer::Framebuffer;
+
+/// Identifies checkpoint files, the last byte is the format version.
+const MAGIC: [u8; 8] = *b"RTCKPT\0\x02";
+
+/// Accumulation state of an interrupted render, see `save` and `load`.
+#[derive(Debug)]
+pub struct Checkpoint {
+    /// Render seed the samples were traced with, resuming with another seed would mix two
+    /// different noise patterns.
+    pub seed: u64,
+    pub framebuffer: Framebuffer,
+}
+
+/// Writes the accumulated samples of `framebuffer` to `path`.
+///
+/// The file is written next to `path` first and then renamed over it, so stopping the process
+/// while saving never leaves a truncated checkpoint behind.
+pub fn save<P: AsRef<Path>>(path: P, seed: u64, framebuffer: &Framebuffer) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+
+    let mut writer = BufWriter::new(File::create(&temporary)?);
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&seed.to_le_bytes())?;
+    writer.write_all(&(framebuffer.width() as u64).to_le_bytes())?;
+    writer.write_all(&(framebuffer.height() as u64).to_le_bytes())?;
+    for value in framebuffer.data() {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    writer.into_inner().map_err(|err| err.into_error())?.sync_all()?;
+
+    fs::rename(&temporary, path)
+}
+
+/// Reads back a checkpoint written by `save`.
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Checkpoint> {
+    let bytes = fs::read(path)?;
+    let invalid = || Error::new(ErrorKind::InvalidData, "not a raytracer checkpoint");
+
+    let (magic, rest) = bytes.split_at_checked(MAGIC.len()).ok_or_else(invalid)?;
+    if magic != MAGIC {
+        return Err(invalid());
+    }
+
+    let mut values = rest.chunks_exact(8).map(|chunk| chunk.try_into().unwrap());
+    let mut header = || values.next().map(u64::from_le_bytes).ok_or_else(invalid);
+    let seed = header()?;
+    let width = header()? as usize;
+    let height = header()? as usize;
+
+    let data: Vec<f64> = values.map(f64::from_le_bytes).collect();
+    if rest.len() % 8 != 0 {
+        return Err(invalid());
+    }
+    let framebuffer = Framebuffer::from_raw(width, height, data).ok_or_else(invalid)?;
+
+    Ok(Checkpoint { seed, framebuffer })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temporary_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("checkpoint-{}-{}.ckpt", std::process::id(), name))
+    }
+
+    fn framebuffer() -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(3, 2);
+        for (i, value) in framebuffer.data_mut().iter_mut().enumerate() {
+            *value = i as f64 * 0.25 - 1.0;
+        }
+        framebuffer
+    }
+
+    /// Bytes of a valid checkpoint, edited by `edit`, then loaded back.
+    fn load_edited(name: &str, edit: impl FnOnce(&mut Vec<u8>)) -> io::Result<Checkpoint> {
+        let path = temporary_path(name);
+        save(&path, 7, &framebuffer()).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        edit(&mut bytes);
+        fs::write(&path, bytes).unwrap();
+        let checkpoint = load(&path);
+        fs::remove_file(&path).unwrap();
+        checkpoint
+    }
+
+    #[test]
+    fn round_trip() {
+        let checkpoint = load_edited("round-trip", |_| ()).unwrap();
+        assert_eq!(checkpoint.seed, 7);
+        assert_eq!(checkpoint.framebuffer.width(), 3);
+        assert_eq!(checkpoint.framebuffer.height(), 2);
+        assert_eq!(checkpoint.framebuffer.data(), framebuffer().data());
+    }
+
+    #[test]
+    fn bad_magic_is_rejected() {
+        let err = load_edited("magic", |bytes| bytes[0] = b'X').unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_header_is_rejected() {
+        // magic, seed and half of the width
+        let err = load_edited("header", |bytes| bytes.truncate(MAGIC.len() + 12)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn partial_value_is_rejected() {
+        let err = load_edited("partial", |bytes| bytes.extend_from_slice(&[0; 3])).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let err = load_edited("missing", |bytes| bytes.truncate(bytes.len() - 8)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
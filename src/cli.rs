@@ -0,0 +1,370 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::aov::{layer_path, Aov};
+use crate::denoise::Denoiser;
+use crate::integrator::{
+    AlbedoIntegrator, AmbientOcclusionIntegrator, BounceIntegrator, DepthIntegrator, Integrator,
+    IntegratorKind, MaterialIdIntegrator, NormalIntegrator, PathTracer,
+};
+use crate::raytracer::{ExecutionMethod, RaytracerOptions};
+use crate::sampling::AdaptiveSampling;
+use crate::tonemap::{ToneMapOperator, ToneMapping};
+
+pub const USAGE: &str = "\
+Usage: raytracing_series [OPTIONS]
+
+Options:
+  -o, --output <PATH>      Output image, format picked from the extension (.exr and .hdr keep
+                           the linear radiance) [default: test.png]
+  -w, --width <PIXELS>     Image width
+      --height <PIXELS>    Image height
+  -a, --aspect <RATIO>     Aspect ratio, either as `16:9` or `1.777`
+  -s, --samples <N>        Samples per pixel, the most a pixel can get with adaptive sampling
+  -d, --depth <N>          Maximum ray bounce depth
+      --roulette <N>       Bounce from which dim paths are terminated by Russian roulette,
+                           a value of at least the depth turns it off [default: 5]
+      --integrator <NAME>  What to render: path (the image), or a debug view of the first hit:
+                           normals, depth, albedo, material-id, bounces (path length) or
+                           ao (ambient occlusion) [default: path]
+      --ao-distance <D>    Ambient occlusion: ignore occluders farther than D [default: no limit]
+      --aov <LIST>         Also save these passes of the first hit, comma separated: normal,
+                           albedo, depth, position, object-id, or all. Added as layers to .exr
+                           output, saved next to the image (`test.normal.png`) otherwise
+      --denoise            Denoise the saved image, guided by the normal, albedo and depth of
+                           the first hit
+      --denoise-radius <PIXELS>
+                           Denoising: pixels averaged on every side of a pixel [default: 5]
+      --keep-raw           Denoising: also save the noisy image, as `test.raw.png`
+      --noise-threshold <T>
+                           Adaptive sampling: stop sampling a pixel once the relative standard
+                           error of its luminance is below T, e.g. 0.01
+      --min-samples <N>    Adaptive sampling: samples every pixel gets first [default: 16]
+      --heatmap <PATH>     Save an image of the samples taken by every pixel
+  -j, --threads <N>        Render threads, 0 uses one per core [default: 1]
+      --scene <SCENE>      Built-in scene name or path to a .toml scene file [default: random-spheres]
+      --tonemap <OPERATOR> Tone mapping for 8-bit output: clamp, reinhard or aces [default: clamp]
+      --exposure <STOPS>   Exposure adjustment applied before tone mapping [default: 0]
+      --seed <N>           Seed for the scene generation and the render, random if omitted
+      --pass <N>           Render progressively, N samples per pixel at a time, saving the
+                           output after every pass
+      --checkpoint <PATH>  Save the accumulated samples after every pass, to resume the render later
+      --resume <PATH>      Continue the render saved in a checkpoint, keeps updating it unless
+                           `--checkpoint` is given
+  -h, --help               Print this help
+
+Width, height and aspect ratio not given on the command line are taken from the scene.";
+
+/// What the command line asked the executable to do.
+pub enum Command {
+    Render(Box<Arguments>),
+    Help,
+}
+
+/// Validated command line arguments. Values left as `None` fall back to the scene's own settings.
+#[derive(Debug)]
+pub struct Arguments {
+    output: String,
+    width: Option<usize>,
+    height: Option<usize>,
+    aspect_ratio: Option<f64>,
+    samples: Option<u32>,
+    max_depth: Option<u32>,
+    roulette_depth: Option<u32>,
+    integrator: IntegratorKind,
+    ao_distance: f64,
+    aovs: Vec<Aov>,
+    denoise: bool,
+    denoise_radius: usize,
+    keep_raw: bool,
+    threads: usize,
+    scene: String,
+    seed: Option<u64>,
+    tone_mapping: ToneMapOperator,
+    exposure: f64,
+    pass_samples: Option<u32>,
+    noise_threshold: Option<f64>,
+    min_samples: u32,
+    heatmap: Option<String>,
+    checkpoint: Option<String>,
+    resume: Option<String>,
+}
+
+impl Arguments {
+    /// Parses the arguments, excluding the executable name.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, CliError> {
+        let mut arguments = Arguments {
+            output: String::from("test.png"),
+            width: None,
+            height: None,
+            aspect_ratio: None,
+            samples: None,
+            max_depth: None,
+            roulette_depth: None,
+            integrator: IntegratorKind::Path,
+            ao_distance: f64::INFINITY,
+            aovs: Vec::new(),
+            denoise: false,
+            denoise_radius: Denoiser::DEFAULT_RADIUS,
+            keep_raw: false,
+            threads: 1,
+            scene: String::from("random-spheres"),
+            seed: None,
+            tone_mapping: ToneMapOperator::Clamp,
+            exposure: 0.0,
+            pass_samples: None,
+            noise_threshold: None,
+            min_samples: 16,
+            heatmap: None,
+            checkpoint: None,
+            resume: None,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // accept both `--flag value` and `--flag=value`
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+                _ => (arg, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| CliError::new(format!("missing value for `{}`", flag)))
+            };
+
+            match flag.as_str() {
+                "-h" | "--help" => return Ok(Command::Help),
+                "-o" | "--output" => arguments.output = value()?,
+                "-w" | "--width" => arguments.width = Some(parse_positive(&flag, &value()?)?),
+                "--height" => arguments.height = Some(parse_positive(&flag, &value()?)?),
+                "-a" | "--aspect" => arguments.aspect_ratio = Some(parse_aspect_ratio(&value()?)?),
+                "-s" | "--samples" => arguments.samples = Some(parse_positive(&flag, &value()?)?),
+                "-d" | "--depth" => arguments.max_depth = Some(parse_positive(&flag, &value()?)?),
+                "--roulette" => arguments.roulette_depth = Some(parse_number(&flag, &value()?)?),
+                "--integrator" => arguments.integrator = value()?.parse().map_err(CliError::new)?,
+                "--ao-distance" => arguments.ao_distance = parse_positive(&flag, &value()?)?,
+                "--aov" => arguments.aovs = parse_aovs(&value()?)?,
+                "--denoise" => arguments.denoise = true,
+                "--denoise-radius" => arguments.denoise_radius = parse_positive(&flag, &value()?)?,
+                "--keep-raw" => arguments.keep_raw = true,
+                "-j" | "--threads" => arguments.threads = parse_number(&flag, &value()?)?,
+                "--scene" => arguments.scene = value()?,
+                "--seed" => arguments.seed = Some(parse_number(&flag, &value()?)?),
+                "--tonemap" => arguments.tone_mapping = value()?.parse().map_err(CliError::new)?,
+                "--exposure" => {
+                    arguments.exposure = parse_number(&flag, &value()?)?;
+                    if !arguments.exposure.is_finite() {
+                        return Err(CliError::new(String::from("`--exposure` must be finite")));
+                    }
+                }
+                "--pass" => arguments.pass_samples = Some(parse_positive(&flag, &value()?)?),
+                "--noise-threshold" => arguments.noise_threshold = Some(parse_positive(&flag, &value()?)?),
+                "--min-samples" => arguments.min_samples = parse_positive(&flag, &value()?)?,
+                "--heatmap" => arguments.heatmap = Some(value()?),
+                "--checkpoint" => arguments.checkpoint = Some(value()?),
+                "--resume" => arguments.resume = Some(value()?),
+                _ if flag.starts_with('-') => {
+                    return Err(CliError::new(format!("unknown option `{}`", flag)))
+                }
+                _ => return Err(CliError::new(format!("unexpected argument `{}`", flag))),
+            }
+        }
+
+        if arguments.width.is_some() && arguments.height.is_some() && arguments.aspect_ratio.is_some() {
+            return Err(CliError::new(String::from(
+                "`--width`, `--height` and `--aspect` can't all be given at once",
+            )));
+        }
+        if arguments.keep_raw && !arguments.denoise {
+            return Err(CliError::new(String::from("`--keep-raw` needs `--denoise`")));
+        }
+        for path in std::iter::once(&arguments.output).chain(&arguments.heatmap) {
+            if image::ImageFormat::from_path(path).is_err() {
+                return Err(CliError::new(format!("unsupported output format for `{}`", path)));
+            }
+        }
+
+        Ok(Command::Render(Box::new(arguments)))
+    }
+
+    #[inline(always)]
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    #[inline(always)]
+    pub fn samples(&self) -> Option<u32> {
+        self.samples
+    }
+
+    #[inline(always)]
+    pub fn max_depth(&self) -> Option<u32> {
+        self.max_depth
+    }
+
+    /// The integrator picked with `--integrator`, set up with the related options.
+    pub fn integrator(&self) -> Arc<dyn Integrator> {
+        let path_tracer = PathTracer::new(self.roulette_depth.unwrap_or(PathTracer::DEFAULT_ROULETTE_DEPTH));
+        match self.integrator {
+            IntegratorKind::Path => Arc::new(path_tracer),
+            IntegratorKind::Normals => Arc::new(NormalIntegrator),
+            IntegratorKind::Depth => Arc::new(DepthIntegrator),
+            IntegratorKind::Albedo => Arc::new(AlbedoIntegrator),
+            IntegratorKind::MaterialId => Arc::new(MaterialIdIntegrator),
+            IntegratorKind::Bounces => Arc::new(BounceIntegrator::new(path_tracer)),
+            IntegratorKind::AmbientOcclusion => Arc::new(AmbientOcclusionIntegrator::new(self.ao_distance)),
+        }
+    }
+
+    /// AOVs asked for with `--aov`, in the order given.
+    #[inline(always)]
+    pub fn aovs(&self) -> &[Aov] {
+        &self.aovs
+    }
+
+    /// Denoiser asked for with `--denoise`, `None` otherwise.
+    pub fn denoiser(&self) -> Option<Denoiser> {
+        self.denoise.then(|| Denoiser::new(self.denoise_radius))
+    }
+
+    /// Where to save the image before denoising, with `--keep-raw`.
+    pub fn raw_output(&self) -> Option<String> {
+        self.keep_raw
+            .then(|| layer_path(Path::new(&self.output), "raw").to_string_lossy().into_owned())
+    }
+
+    #[inline(always)]
+    pub fn scene(&self) -> &str {
+        &self.scene
+    }
+
+    #[inline(always)]
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    #[inline(always)]
+    pub fn pass_samples(&self) -> Option<u32> {
+        self.pass_samples
+    }
+
+    /// Adaptive sampling settings, `None` unless a noise threshold was given.
+    pub fn adaptive_sampling(&self) -> Option<AdaptiveSampling> {
+        self.noise_threshold
+            .map(|threshold| AdaptiveSampling::new(self.min_samples, threshold))
+    }
+
+    #[inline(always)]
+    pub fn heatmap(&self) -> Option<&str> {
+        self.heatmap.as_deref()
+    }
+
+    /// Where to save the checkpoints, the resumed one by default.
+    pub fn checkpoint(&self) -> Option<&str> {
+        self.checkpoint.as_deref().or(self.resume.as_deref())
+    }
+
+    #[inline(always)]
+    pub fn resume(&self) -> Option<&str> {
+        self.resume.as_deref()
+    }
+
+    /// Width and height of the image, filling in whatever was not given from the scene's
+    /// `aspect_ratio` and `width`. `None` if the resolution wasn't touched on the command line.
+    pub fn image_size(&self, aspect_ratio: f64, width: usize) -> Option<(usize, usize)> {
+        let height_of = |w: usize, aspect: f64| usize::max((w as f64 / aspect) as usize, 1);
+        match (self.width, self.height, self.aspect_ratio) {
+            (None, None, None) => None,
+            (Some(w), Some(h), _) => Some((w, h)),
+            (Some(w), None, aspect) => Some((w, height_of(w, aspect.unwrap_or(aspect_ratio)))),
+            (None, Some(h), aspect) => {
+                let aspect = aspect.unwrap_or(aspect_ratio);
+                Some((usize::max((h as f64 * aspect).round() as usize, 1), h))
+            }
+            (None, None, Some(aspect)) => Some((width, height_of(width, aspect))),
+        }
+    }
+
+    pub fn tone_mapping(&self) -> ToneMapping {
+        ToneMapping::new(self.tone_mapping, self.exposure)
+    }
+
+    pub fn raytracer_options(&self) -> RaytracerOptions {
+        let options = match self.threads {
+            1 => RaytracerOptions::new(ExecutionMethod::Single),
+            n => RaytracerOptions::new(ExecutionMethod::Parallel).with_threads(n),
+        };
+        match self.pass_samples {
+            Some(pass_samples) => options.with_pass_samples(pass_samples),
+            None => options,
+        }
+    }
+}
+
+fn parse_number<T: FromStr>(flag: &str, value: &str) -> Result<T, CliError> {
+    value
+        .parse()
+        .map_err(|_| CliError::new(format!("invalid value `{}` for `{}`", value, flag)))
+}
+
+fn parse_positive<T: FromStr + Default + PartialOrd>(flag: &str, value: &str) -> Result<T, CliError> {
+    let number: T = parse_number(flag, value)?;
+    if number <= T::default() {
+        return Err(CliError::new(format!("`{}` must be greater than 0", flag)));
+    }
+    Ok(number)
+}
+
+fn parse_aspect_ratio(value: &str) -> Result<f64, CliError> {
+    let ratio = match value.split_once(':') {
+        Some((w, h)) => {
+            let w: f64 = parse_number("--aspect", w)?;
+            let h: f64 = parse_number("--aspect", h)?;
+            w / h
+        }
+        None => parse_number("--aspect", value)?,
+    };
+    if !ratio.is_finite() || ratio <= 0.0 {
+        return Err(CliError::new(format!("invalid aspect ratio `{}`", value)));
+    }
+    Ok(ratio)
+}
+
+/// Comma separated AOV names, or `all` of them. Names given twice are kept once.
+fn parse_aovs(value: &str) -> Result<Vec<Aov>, CliError> {
+    if value == "all" {
+        return Ok(Aov::ALL.to_vec());
+    }
+    let mut aovs = Vec::new();
+    for name in value.split(',') {
+        let aov: Aov = name.trim().parse().map_err(CliError::new)?;
+        if !aovs.contains(&aov) {
+            aovs.push(aov);
+        }
+    }
+    Ok(aovs)
+}
+
+#[derive(Debug)]
+pub struct CliError {
+    message: String,
+}
+
+impl CliError {
+    pub fn new(message: String) -> Self {
+        CliError { message }
+    }
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for CliError {}
@@ -1,5 +1,7 @@
 pub use super::vec3::Vec3 as Color;
 
+use super::utilities::mix;
+
 impl Color {
     #[inline(always)]
     pub fn r(&self) -> &f64 {
@@ -31,6 +33,20 @@ impl Color {
         self.z_mut()
     }
 
+    /// Relative luminance of a linear Rec. 709 color.
+    #[inline(always)]
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r() + 0.7152 * self.g() + 0.0722 * self.b()
+    }
+
+    /// Arbitrary but fixed color of an object or material `id`, bright enough to tell apart
+    /// from black.
+    pub fn from_id(id: u64) -> Color {
+        let hash = mix(id);
+        let channel = |shift: u32| 0.2 + 0.8 * ((hash >> shift) & 0xff) as f64 / 255.0;
+        Color::new(channel(0), channel(8), channel(16))
+    }
+
     pub fn black() -> Color {
         Color::zero()
     }
@@ -59,4 +75,50 @@ impl Color {
     pub fn gamma_to_linear(color: &Color) -> Color {
         Color::new(color.x().powi(2), color.y().powi(2), color.z().powi(2))
     }
+
+    /// sRGB transfer function (IEC 61966-2-1), for 0..1 linear values.
+    pub fn linear_to_srgb(color: &Color) -> Color {
+        let encode = |c: f64| {
+            if c <= 0.0031308 {
+                12.92 * c
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        };
+        Color::new(encode(*color.r()), encode(*color.g()), encode(*color.b()))
+    }
+
+    /// Inverse of `linear_to_srgb`.
+    pub fn srgb_to_linear(color: &Color) -> Color {
+        let decode = |c: f64| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        Color::new(decode(*color.r()), decode(*color.g()), decode(*color.b()))
+    }
+
+    /// False color ramp from black (0) through purple, red and yellow to white (1), values in
+    /// 0..1 are display (sRGB encoded) colors.
+    pub fn heat(t: f64) -> Color {
+        const RAMP: [[f64; 3]; 5] = [
+            [0.0, 0.0, 0.0],
+            [0.2, 0.0, 0.6],
+            [0.9, 0.1, 0.2],
+            [1.0, 0.7, 0.0],
+            [1.0, 1.0, 1.0],
+        ];
+
+        let position = t.clamp(0.0, 1.0) * (RAMP.len() - 1) as f64;
+        let index = usize::min(position as usize, RAMP.len() - 2);
+        let f = position - index as f64;
+        let (a, b) = (RAMP[index], RAMP[index + 1]);
+        Color::new(
+            (1.0 - f) * a[0] + f * b[0],
+            (1.0 - f) * a[1] + f * b[1],
+            (1.0 - f) * a[2] + f * b[2],
+        )
+    }
 }
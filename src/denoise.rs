@@ -0,0 +1,233 @@
+use rayon::prelude::*;
+
+use super::aov::{Aov, Aovs};
+use super::color::Color;
+use super::framebuffer::Framebuffer;
+use super::vec3::Vec3;
+
+/// Lowest albedo the radiance is divided by before filtering, so black surfaces (lights,
+/// background) don't divide by zero.
+const MIN_ALBEDO: f64 = 0.01;
+
+/// Joint bilateral filter removing the noise of a render, guided by its normal, albedo and
+/// depth AOVs.
+///
+/// Every pixel becomes a weighted average of its neighbors within `radius`, where neighbors
+/// facing another way, made of another material, at another depth or much brighter than the
+/// pixel's own noise level explains get little weight, so edges and shadows stay sharp while
+/// flat areas are smoothed. The albedo is divided out of the radiance before filtering and
+/// multiplied back after, which keeps texture detail the filter would otherwise blur.
+#[derive(Debug, Clone, Copy)]
+pub struct Denoiser {
+    radius: usize,
+    /// How different the normals can be, as the exponent of their cosine.
+    normal_exponent: f64,
+    /// Albedo difference halving the weight of a neighbor, roughly.
+    sigma_albedo: f64,
+    /// Depth difference, relative to the depth of the pixel.
+    sigma_depth: f64,
+    /// Luminance difference, in standard errors of the difference.
+    sigma_luminance: f64,
+}
+
+impl Denoiser {
+    /// AOVs the denoiser needs along the image.
+    pub const GUIDES: [Aov; 3] = [Aov::Normal, Aov::Albedo, Aov::Depth];
+    pub const DEFAULT_RADIUS: usize = 5;
+
+    /// Filters over a square window of `2 * radius + 1` pixels a side.
+    pub fn new(radius: usize) -> Self {
+        Denoiser {
+            radius,
+            normal_exponent: 64.0,
+            sigma_albedo: 0.1,
+            sigma_depth: 0.05,
+            sigma_luminance: 4.0,
+        }
+    }
+
+    #[inline(always)]
+    pub fn radius(&self) -> usize {
+        self.radius
+    }
+
+    /// Denoised copy of `framebuffer`, keeping its sample counts, filtered on the current rayon
+    /// thread pool. `None` if `aovs` lacks one of the `GUIDES` or doesn't match the
+    /// framebuffer's size.
+    pub fn denoise(&self, framebuffer: &Framebuffer, aovs: &Aovs) -> Option<Framebuffer> {
+        let (width, height) = (framebuffer.width(), framebuffer.height());
+        if (aovs.width(), aovs.height()) != (width, height) {
+            return None;
+        }
+        let to_vec3 = |values: &[f32]| Vec3::new(values[0] as f64, values[1] as f64, values[2] as f64);
+        let normals: Vec<Vec3> = aovs.layer(Aov::Normal)?.chunks_exact(3).map(to_vec3).collect();
+        let albedos: Vec<Color> = aovs.layer(Aov::Albedo)?.chunks_exact(3).map(to_vec3).collect();
+        let depths = aovs.layer(Aov::Depth)?;
+
+        let data = framebuffer.data();
+        let pixels: Vec<Pixel> = data
+            .chunks_exact(Framebuffer::STRIDE)
+            .zip(&normals)
+            .zip(albedos.iter().zip(depths))
+            .map(|((pixel, normal), (albedo, depth))| Pixel::new(pixel, normal, albedo, *depth as f64))
+            .collect();
+        let deviations = smoothed_deviations(&pixels, width, height);
+
+        let mut denoised = data.to_vec();
+        denoised
+            .par_chunks_exact_mut(width * Framebuffer::STRIDE)
+            .enumerate()
+            .for_each(|(j, line)| {
+                for (i, pixel) in line.chunks_exact_mut(Framebuffer::STRIDE).enumerate() {
+                    let samples = pixel[Framebuffer::SAMPLES];
+                    if samples == 0.0 {
+                        continue;
+                    }
+                    let color = self.filter_pixel(&pixels, &deviations, width, height, i, j) * samples;
+                    pixel[0] = *color.r();
+                    pixel[1] = *color.g();
+                    pixel[2] = *color.b();
+                }
+            });
+
+        Framebuffer::from_raw(width, height, denoised)
+    }
+
+    /// Filtered radiance of pixel (`i`, `j`).
+    fn filter_pixel(&self, pixels: &[Pixel], deviations: &[f64], width: usize, height: usize, i: usize, j: usize) -> Color {
+        let center = &pixels[j * width + i];
+        let center_deviation = deviations[j * width + i];
+        let sigma_spatial = (self.radius as f64 * 0.5).max(0.5);
+
+        let mut sum = Color::black();
+        let mut weights = 0.0;
+        for y in j.saturating_sub(self.radius)..(j + self.radius + 1).min(height) {
+            for x in i.saturating_sub(self.radius)..(i + self.radius + 1).min(width) {
+                let other = &pixels[y * width + x];
+                if other.samples == 0.0 {
+                    continue;
+                }
+                let (dx, dy) = (x as f64 - i as f64, y as f64 - j as f64);
+                let spatial = -(dx * dx + dy * dy) / (2.0 * sigma_spatial * sigma_spatial);
+                let deviation = center_deviation.hypot(deviations[y * width + x]);
+                let weight = spatial.exp() * self.similarity(center, other, deviation);
+                sum += weight * other.irradiance;
+                weights += weight;
+            }
+        }
+
+        // the pixel itself always has a weight of 1
+        center.remodulate(&(sum / weights))
+    }
+
+    /// Weight, between 0 and 1, of `other` in the filtered value of `center`, given the
+    /// standard error of their luminance difference.
+    fn similarity(&self, center: &Pixel, other: &Pixel, deviation: f64) -> f64 {
+        // background pixels only mix with each other
+        let normal = match (center.normal.near_zero(), other.normal.near_zero()) {
+            (true, true) => 1.0,
+            (false, false) => Vec3::dot(&center.normal, &other.normal).max(0.0).powf(self.normal_exponent),
+            _ => 0.0,
+        };
+        if normal == 0.0 {
+            return 0.0;
+        }
+
+        let albedo = -(center.albedo - other.albedo).len_squared() / (2.0 * self.sigma_albedo * self.sigma_albedo);
+        let depth = match (center.depth.is_finite(), other.depth.is_finite()) {
+            (true, true) => -(center.depth - other.depth).abs() / (self.sigma_depth * center.depth).max(f64::EPSILON),
+            (false, false) => 0.0,
+            _ => return 0.0,
+        };
+        // without a noise estimate (single samples) the guides alone decide
+        let luminance = if deviation > 0.0 {
+            -(center.radiance.luminance() - other.radiance.luminance()).abs() / (self.sigma_luminance * deviation)
+        } else {
+            0.0
+        };
+
+        normal * (albedo + depth + luminance).exp()
+    }
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser::new(Denoiser::DEFAULT_RADIUS)
+    }
+}
+
+/// What the filter knows about one pixel.
+struct Pixel {
+    radiance: Color,
+    /// `radiance` with the albedo divided out.
+    irradiance: Color,
+    /// Variance of the luminance of the average radiance, 0 when unknown.
+    variance: f64,
+    samples: f64,
+    normal: Vec3,
+    albedo: Color,
+    depth: f64,
+}
+
+impl Pixel {
+    fn new(pixel: &[f64], normal: &Vec3, albedo: &Color, depth: f64) -> Self {
+        let samples = pixel[Framebuffer::SAMPLES];
+        let (radiance, variance) = if samples > 0.0 {
+            let radiance = Color::new(pixel[0], pixel[1], pixel[2]) / samples;
+            let mean = radiance.luminance();
+            let variance = if samples > 1.0 {
+                ((pixel[Framebuffer::SQUARES] / samples - mean * mean) / (samples - 1.0)).max(0.0)
+            } else {
+                0.0
+            };
+            (radiance, variance)
+        } else {
+            (Color::black(), 0.0)
+        };
+
+        let irradiance = Color::new(
+            *radiance.r() / albedo.r().max(MIN_ALBEDO),
+            *radiance.g() / albedo.g().max(MIN_ALBEDO),
+            *radiance.b() / albedo.b().max(MIN_ALBEDO),
+        );
+
+        Pixel {
+            radiance,
+            irradiance,
+            variance,
+            samples,
+            normal: if normal.near_zero() { Vec3::zero() } else { normal.normalized() },
+            albedo: *albedo,
+            depth,
+        }
+    }
+
+    /// Radiance of `irradiance` reflected by this pixel's albedo, reverses the division done
+    /// in `new`.
+    fn remodulate(&self, irradiance: &Color) -> Color {
+        Color::new(
+            *irradiance.r() * self.albedo.r().max(MIN_ALBEDO),
+            *irradiance.g() * self.albedo.g().max(MIN_ALBEDO),
+            *irradiance.b() * self.albedo.b().max(MIN_ALBEDO),
+        )
+    }
+}
+
+/// Standard error of the luminance of every pixel, averaged over its 3x3 neighborhood: the
+/// estimate of a single pixel is too noisy itself at low sample counts.
+fn smoothed_deviations(pixels: &[Pixel], width: usize, height: usize) -> Vec<f64> {
+    (0..width * height)
+        .into_par_iter()
+        .map(|index| {
+            let (i, j) = (index % width, index / width);
+            let (mut sum, mut count) = (0.0, 0);
+            for y in j.saturating_sub(1)..(j + 2).min(height) {
+                for x in i.saturating_sub(1)..(i + 2).min(width) {
+                    sum += pixels[y * width + x].variance;
+                    count += 1;
+                }
+            }
+            (sum / count as f64).sqrt()
+        })
+        .collect()
+}
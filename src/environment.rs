@@ -0,0 +1,129 @@
+use std::f64::consts::PI;
+use std::path::Path;
+
+use super::color::Color;
+use super::utilities::deg_to_rad;
+use super::vec3::Vec3;
+
+/// Radiance coming from infinitely far away, picked up by the rays that escape the scene.
+pub trait Environment: Send + Sync {
+    fn value(&self, direction: &Vec3) -> Color;
+}
+
+/// Same color in every direction. Black turns the environment off, for scenes lit only by
+/// their own light sources.
+pub struct ConstantEnvironment {
+    color: Color,
+}
+
+impl ConstantEnvironment {
+    pub fn new(color: Color) -> Self {
+        ConstantEnvironment { color }
+    }
+}
+
+impl Environment for ConstantEnvironment {
+    fn value(&self, _direction: &Vec3) -> Color {
+        self.color
+    }
+}
+
+/// Vertical gradient going from `bottom` straight down to `top` straight up.
+pub struct GradientEnvironment {
+    bottom: Color,
+    top: Color,
+}
+
+impl GradientEnvironment {
+    pub fn new(bottom: Color, top: Color) -> Self {
+        GradientEnvironment { bottom, top }
+    }
+
+    /// The white to light blue sky of the books.
+    pub fn sky() -> Self {
+        GradientEnvironment::new(Color::white(), Color::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Environment for GradientEnvironment {
+    fn value(&self, direction: &Vec3) -> Color {
+        // first map a from -1..1 to 0..1
+        let a = 0.5 * (direction.normalized().y() + 1.0);
+        // now lerp the colors
+        (1.0 - a) * self.bottom + a * self.top
+    }
+}
+
+/// Equirectangular (latitude/longitude) environment map, usually an HDR panorama.
+///
+/// The center of the image looks down -z, the top row straight up.
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+    intensity: f64,
+    rotation: f64,
+}
+
+impl EnvironmentMap {
+    /// Loads any format the `image` crate can decode; Radiance `.hdr` and OpenEXR `.exr` keep
+    /// their full dynamic range.
+    pub fn load<P: AsRef<Path>>(path: P) -> image::ImageResult<Self> {
+        let image = image::open(path)?.into_rgb32f();
+        Ok(EnvironmentMap {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            data: image.into_raw(),
+            intensity: 1.0,
+            rotation: 0.0,
+        })
+    }
+
+    /// Scales the radiance of the map.
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Rotates the map around the vertical axis, in degrees.
+    pub fn with_rotation(mut self, degrees: f64) -> Self {
+        self.rotation = deg_to_rad(degrees);
+        self
+    }
+
+    fn texel(&self, x: usize, y: usize) -> Color {
+        let index = (y * self.width + x) * 3;
+        Color::new(
+            self.data[index] as f64,
+            self.data[index + 1] as f64,
+            self.data[index + 2] as f64,
+        )
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn value(&self, direction: &Vec3) -> Color {
+        let d = direction.normalized();
+        let phi = f64::atan2(*d.x(), -*d.z()) + self.rotation;
+        let theta = f64::acos(d.y().clamp(-1.0, 1.0));
+
+        let u = (phi / (2.0 * PI) + 0.5).rem_euclid(1.0);
+        let v = theta / PI;
+
+        // bilinear filtering, wrapping around horizontally
+        let x = u * self.width as f64 - 0.5;
+        let y = (v * self.height as f64 - 0.5).clamp(0.0, (self.height - 1) as f64);
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let (fx, fy) = (x - x0, y - y0);
+
+        let wrap = |x: f64| (x as i64).rem_euclid(self.width as i64) as usize;
+        let (x0, x1) = (wrap(x0), wrap(x0 + 1.0));
+        let (y0, y1) = (y0 as usize, usize::min(y0 as usize + 1, self.height - 1));
+
+        let top = (1.0 - fx) * self.texel(x0, y0) + fx * self.texel(x1, y0);
+        let bottom = (1.0 - fx) * self.texel(x0, y1) + fx * self.texel(x1, y1);
+
+        self.intensity * ((1.0 - fy) * top + fy * bottom)
+    }
+}
@@ -0,0 +1,149 @@
+use std::path::Path;
+
+use image::{ImageFormat, ImageResult, Rgb32FImage, RgbImage};
+
+use super::color::Color;
+use super::tonemap::ToneMapping;
+
+/// Accumulated linear RGB radiance of every pixel, row by row.
+///
+/// Every pixel stores the sum of its samples, the sum of their squared luminance (to estimate
+/// the variance) and how many there are, so rendering can go on in several passes and the
+/// state can be checkpointed. Values are kept unclamped,
+/// high dynamic range formats get the full radiance while 8-bit formats are tone mapped only
+/// when saving.
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    data: Vec<f64>,
+}
+
+impl Framebuffer {
+    /// Color components of the saved images.
+    pub const COMPONENTS: usize = 3;
+    /// Index of the squared luminance sum in a pixel of `data`, after the radiance sums.
+    pub const SQUARES: usize = Framebuffer::COMPONENTS;
+    /// Index of the sample count in a pixel of `data`.
+    pub const SAMPLES: usize = Framebuffer::COMPONENTS + 1;
+    /// Values stored per pixel in `data`.
+    pub const STRIDE: usize = Framebuffer::COMPONENTS + 2;
+
+    pub fn new(width: usize, height: usize) -> Self {
+        Framebuffer {
+            width,
+            height,
+            data: vec![0.0; width * height * Framebuffer::STRIDE],
+        }
+    }
+
+    /// Framebuffer holding previously accumulated `data`, `None` if it doesn't match the size.
+    pub fn from_raw(width: usize, height: usize, data: Vec<f64>) -> Option<Self> {
+        let len = width.checked_mul(height)?.checked_mul(Framebuffer::STRIDE)?;
+        (data.len() == len).then_some(Framebuffer {
+            width,
+            height,
+            data,
+        })
+    }
+
+    #[inline(always)]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline(always)]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    #[inline(always)]
+    pub fn data(&self) -> &[f64] {
+        &self.data
+    }
+
+    #[inline(always)]
+    pub fn data_mut(&mut self) -> &mut [f64] {
+        &mut self.data
+    }
+
+    /// Number of samples accumulated by the pixel at column `i`, row `j`.
+    pub fn samples(&self, i: usize, j: usize) -> u32 {
+        let index = (j * self.width + i) * Framebuffer::STRIDE;
+        self.data[index + Framebuffer::SAMPLES] as u32
+    }
+
+    /// Lowest number of samples accumulated by any pixel.
+    pub fn min_samples(&self) -> u32 {
+        self.data
+            .chunks_exact(Framebuffer::STRIDE)
+            .map(|pixel| pixel[Framebuffer::SAMPLES] as u32)
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Average radiance of the pixel at column `i`, row `j`, black if it has no samples yet.
+    pub fn pixel(&self, i: usize, j: usize) -> Color {
+        let index = (j * self.width + i) * Framebuffer::STRIDE;
+        average(&self.data[index..index + Framebuffer::STRIDE])
+    }
+
+    /// Average radiance of every pixel, as `COMPONENTS` `f32` values each.
+    pub fn radiance(&self) -> Vec<f32> {
+        self.data
+            .chunks_exact(Framebuffer::STRIDE)
+            .flat_map(|pixel| {
+                let color = average(pixel);
+                [*color.r() as f32, *color.g() as f32, *color.b() as f32]
+            })
+            .collect()
+    }
+
+    /// Tone mapped, sRGB encoded 8-bit version of the image.
+    pub fn to_rgb8(&self, tone_mapping: &ToneMapping) -> Vec<u8> {
+        self.data
+            .chunks_exact(Framebuffer::STRIDE)
+            .flat_map(|pixel| tone_mapping.encode(&average(pixel)))
+            .collect()
+    }
+
+    /// False color map of the samples taken by every pixel, from black for none to white for
+    /// `max_samples`.
+    pub fn sample_heatmap(&self, max_samples: u32) -> RgbImage {
+        let pixels = self.data.chunks_exact(Framebuffer::STRIDE).flat_map(|pixel| {
+            let color = Color::heat(pixel[Framebuffer::SAMPLES] / max_samples.max(1) as f64);
+            [color.r(), color.g(), color.b()].map(|c| (255.0 * c).round() as u8)
+        });
+
+        RgbImage::from_raw(self.width as u32, self.height as u32, pixels.collect())
+            .expect("framebuffer size matches its dimensions")
+    }
+
+    /// Saves the image, the format is picked from the extension. OpenEXR (`.exr`) and
+    /// Radiance HDR (`.hdr`) files keep the linear radiance, anything else gets 8-bit color
+    /// through `tone_mapping`.
+    pub fn save<P: AsRef<Path>>(&self, path: P, tone_mapping: &ToneMapping) -> ImageResult<()> {
+        let path = path.as_ref();
+        let (width, height) = (self.width as u32, self.height as u32);
+
+        match ImageFormat::from_path(path)? {
+            ImageFormat::OpenExr | ImageFormat::Hdr => {
+                Rgb32FImage::from_raw(width, height, self.radiance())
+                    .expect("framebuffer size matches its dimensions")
+                    .save(path)
+            }
+            _ => RgbImage::from_raw(width, height, self.to_rgb8(tone_mapping))
+                .expect("framebuffer size matches its dimensions")
+                .save(path),
+        }
+    }
+}
+
+/// Average color of one `STRIDE` long pixel of the accumulation data.
+fn average(pixel: &[f64]) -> Color {
+    let samples = pixel[Framebuffer::SAMPLES];
+    if samples == 0.0 {
+        return Color::black();
+    }
+    Color::new(pixel[0], pixel[1], pixel[2]) / samples
+}
@@ -1,13 +1,29 @@
 use std::sync::Arc;
 
+use super::aabb::Aabb;
 use super::material::Material;
 use super::ray::Ray;
 use super::interval::Interval;
 use super::vec3::Vec3;
 use super::Point;
 
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitResult>;
+
+    fn bounding_box(&self) -> Aabb;
+
+    /// Density, over solid angle, of `random(origin)` returning `direction`. Objects that can't
+    /// be sampled as lights keep the default of 0.
+    ///
+    /// Lights are sampled where they are at time 0.
+    fn pdf_value(&self, _origin: &Point, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    /// Random direction from `origin` toward the object, to sample it as a light.
+    fn random(&self, _origin: &Point) -> Vec3 {
+        Vec3::right()
+    }
 }
 
 pub struct HitResult {
@@ -16,6 +32,8 @@ pub struct HitResult {
     material: Arc<dyn Material>,
     t: f64,
     front_face: bool,
+    uv: (f64, f64),
+    object_id: u32,
 }
 
 impl HitResult {
@@ -31,9 +49,31 @@ impl HitResult {
             material,
             t,
             front_face,
+            uv: (0.0, 0.0),
+            object_id: 0,
         }
     }
 
+    /// Sets the surface (texture) coordinates of the hit point.
+    pub fn with_uv(mut self, u: f64, v: f64) -> Self {
+        self.uv = (u, v);
+        self
+    }
+
+    /// Tags the hit with the ID of the scene object it belongs to.
+    pub fn with_object_id(mut self, object_id: u32) -> Self {
+        self.object_id = object_id;
+        self
+    }
+
+    /// Moves the hit to another space, for hittables wrapping an object placed differently.
+    /// `normal` must already face the incoming ray, as the one of the hit does.
+    pub fn transform(mut self, location: Point, normal: Vec3) -> Self {
+        self.location = location;
+        self.normal = normal;
+        self
+    }
+
     #[inline(always)]
     pub fn location(&self) -> &Point {
         &self.location
@@ -44,16 +84,37 @@ impl HitResult {
         &self.normal
     }
 
+    /// Normal on the outside of the surface, whichever side the ray came from.
+    pub fn outward_normal(&self) -> Vec3 {
+        if self.front_face {
+            self.normal
+        } else {
+            -self.normal
+        }
+    }
+
     #[inline(always)]
     pub fn t(&self) -> f64 {
         self.t
     }
 
+    #[inline(always)]
+    pub fn uv(&self) -> (f64, f64) {
+        self.uv
+    }
+
     #[inline(always)]
     pub fn front_face(&self) -> bool {
         self.front_face
     }
 
+    /// ID of the scene object that was hit, 0 if the objects aren't numbered (see
+    /// `HittableList::numbered`).
+    #[inline(always)]
+    pub fn object_id(&self) -> u32 {
+        self.object_id
+    }
+
     #[inline(always)]
     pub fn material(&self) -> Arc<dyn Material> {
         Arc::clone(&self.material)
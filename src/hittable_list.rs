@@ -1,30 +1,73 @@
 use std::sync::Arc;
 
+use super::aabb::Aabb;
 use super::hittable::{Hittable, HitResult};
 use super::interval::Interval;
+use super::ray::Ray;
+use super::utilities::rand;
+use super::vec3::Vec3;
+use super::Point;
 
 pub struct HittableList {
-    objects: Vec<Arc<dyn Hittable>>
+    objects: Vec<Arc<dyn Hittable>>,
+    bbox: Aabb,
 }
 
 impl HittableList {
     pub fn new() -> Self {
         HittableList {
-            objects: vec![]
+            objects: vec![],
+            bbox: Aabb::empty(),
         }
     }
 
     pub fn add(&mut self, object: Arc<dyn Hittable>) {
+        self.bbox = Aabb::enclosing(&self.bbox, &object.bounding_box());
         self.objects.push(object)
     }
 
     pub fn clear(&mut self) {
-        self.objects.clear()
+        self.objects.clear();
+        self.bbox = Aabb::empty();
+    }
+
+    /// The same objects, each one tagging its hits with its position in the list (from 1) as
+    /// the object ID.
+    pub fn numbered(self) -> HittableList {
+        let mut numbered = HittableList::new();
+        for (index, object) in self.objects.into_iter().enumerate() {
+            numbered.add(Arc::new(NumberedObject {
+                object,
+                id: index as u32 + 1,
+            }));
+        }
+        numbered
+    }
+
+    #[inline(always)]
+    pub fn objects(&self) -> &[Arc<dyn Hittable>] {
+        &self.objects
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+impl Default for HittableList {
+    fn default() -> Self {
+        HittableList::new()
     }
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, ray: &crate::ray::Ray, interval: Interval) -> Option<HitResult> {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitResult> {
         let mut hit_record = None;
         let mut closest_so_far = interval.max();
 
@@ -41,7 +84,49 @@ impl Hittable for HittableList {
 
         hit_record
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    /// Every object is picked with the same probability, so the density is their average.
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self.objects.iter().map(|object| object.pdf_value(origin, direction)).sum();
+        sum / self.objects.len() as f64
+    }
+
+    fn random(&self, origin: &Point) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::right();
+        }
+        let index = ((rand() * self.objects.len() as f64) as usize).min(self.objects.len() - 1);
+        self.objects[index].random(origin)
+    }
+}
+
+/// Object of a `numbered` list.
+struct NumberedObject {
+    object: Arc<dyn Hittable>,
+    id: u32,
 }
 
-unsafe impl Send for HittableList{}
-unsafe impl Sync for HittableList{}
\ No newline at end of file
+impl Hittable for NumberedObject {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitResult> {
+        Some(self.object.hit(ray, interval)?.with_object_id(self.id))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Point) -> Vec3 {
+        self.object.random(origin)
+    }
+}
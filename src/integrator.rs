@@ -0,0 +1,287 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use super::color::Color;
+use super::environment::Environment;
+use super::hittable::{HitResult, Hittable};
+use super::hittable_list::HittableList;
+use super::interval::Interval;
+use super::pdf::{CosinePdf, HittablePdf, Pdf};
+use super::ray::Ray;
+use super::utilities::rand;
+
+/// Everything an integrator can look at to compute the color of a camera ray.
+pub struct RenderContext<'a> {
+    pub world: &'a dyn Hittable,
+    /// Objects worth sampling directly as light sources, also part of `world`.
+    pub lights: &'a HittableList,
+    /// What the rays escaping the scene see.
+    pub background: &'a dyn Environment,
+    pub max_depth: u32,
+}
+
+/// Turns a camera ray into the color of one pixel sample.
+pub trait Integrator: Send + Sync {
+    fn color(&self, ray: &Ray, context: &RenderContext) -> Color;
+}
+
+/// Closest hit along `ray`, skipping the ones right at its origin.
+fn first_hit(ray: &Ray, context: &RenderContext) -> Option<HitResult> {
+    // interval starts from 0.001 to hack away the shadow acne problem
+    context.world.hit(ray, Interval::new(0.001, f64::INFINITY))
+}
+
+/// Unidirectional path tracer, the integrator computing the actual image.
+///
+/// Diffuse bounces send half of their rays toward the lights and weight every ray by the
+/// mixture of both densities, which is multiple importance sampling with the balance
+/// heuristic: small lights stop being found by chance only, while large lights and glossy
+/// surfaces stay well sampled by the material.
+///
+/// From bounce `roulette_depth` on, paths are randomly terminated with a probability that grows
+/// as their throughput drops, and the survivors are weighted up to compensate, so dark paths
+/// stop early without biasing the image.
+#[derive(Debug, Clone, Copy)]
+pub struct PathTracer {
+    roulette_depth: u32,
+}
+
+impl PathTracer {
+    /// Bounce from which paths may be terminated by Russian roulette, unless told otherwise.
+    pub const DEFAULT_ROULETTE_DEPTH: u32 = 5;
+
+    /// Depths of `max_depth` or more turn Russian roulette off.
+    pub fn new(roulette_depth: u32) -> Self {
+        PathTracer { roulette_depth }
+    }
+
+    #[inline(always)]
+    pub fn roulette_depth(&self) -> u32 {
+        self.roulette_depth
+    }
+
+    /// Radiance carried back along `ray` and the number of surfaces the path bounced on.
+    fn trace(&self, ray: &Ray, context: &RenderContext) -> (Color, u32) {
+        let mut radiance = Color::black();
+        // how much of the light arriving along `ray` makes it back to the camera
+        let mut throughput = Color::white();
+        let mut ray = *ray;
+
+        for bounce in 0..context.max_depth {
+            let Some(hit) = first_hit(&ray, context) else {
+                return (radiance + throughput * context.background.value(&ray.direction()), bounce);
+            };
+
+            let material = hit.material();
+            radiance += throughput * material.emitted(&hit);
+            let Some(scatter) = material.scatter(&ray, &hit) else {
+                return (radiance, bounce + 1);
+            };
+
+            let (scattered, weight) = match scatter.pdf() {
+                Some(_) if !context.lights.is_empty() => {
+                    let light_pdf = HittablePdf::new(context.lights, *hit.location());
+                    let direction = if rand() < 0.5 {
+                        light_pdf.generate()
+                    } else {
+                        scatter.scattered_ray().direction()
+                    };
+                    let pdf = 0.5 * light_pdf.value(&direction)
+                        + 0.5 * material.scattering_pdf(&ray, &hit, &direction);
+                    if pdf <= 0.0 {
+                        return (radiance, bounce + 1);
+                    }
+                    let scattered = Ray::with_time(*hit.location(), direction, ray.time());
+                    (scattered, material.bsdf(&ray, &hit, &direction) / pdf)
+                }
+                _ => (*scatter.scattered_ray(), scatter.attenuation()),
+            };
+            throughput = throughput * weight;
+
+            if bounce + 1 >= self.roulette_depth {
+                let survival = throughput.r().max(*throughput.g()).max(*throughput.b()).min(1.0);
+                if rand() >= survival {
+                    return (radiance, bounce + 1);
+                }
+                throughput /= survival;
+            }
+            ray = scattered;
+        }
+
+        (radiance, context.max_depth)
+    }
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        PathTracer::new(PathTracer::DEFAULT_ROULETTE_DEPTH)
+    }
+}
+
+impl Integrator for PathTracer {
+    fn color(&self, ray: &Ray, context: &RenderContext) -> Color {
+        self.trace(ray, context).0
+    }
+}
+
+/// Outward facing normal of the first hit, mapped from -1..1 to 0..1 per axis. Surfaces seen
+/// from behind show the opposite color of their front.
+pub struct NormalIntegrator;
+
+impl Integrator for NormalIntegrator {
+    fn color(&self, ray: &Ray, context: &RenderContext) -> Color {
+        match first_hit(ray, context) {
+            Some(hit) => 0.5 * (hit.outward_normal() + Color::white()),
+            None => Color::black(),
+        }
+    }
+}
+
+/// Distance from the camera to the first hit, unscaled: save it as `.exr` or bring it into
+/// range with the exposure. Rays hitting nothing are black.
+pub struct DepthIntegrator;
+
+impl Integrator for DepthIntegrator {
+    fn color(&self, ray: &Ray, context: &RenderContext) -> Color {
+        match first_hit(ray, context) {
+            Some(hit) => hit.t() * ray.direction().len() * Color::white(),
+            None => Color::black(),
+        }
+    }
+}
+
+/// Base color of the first hit, without any lighting.
+pub struct AlbedoIntegrator;
+
+impl Integrator for AlbedoIntegrator {
+    fn color(&self, ray: &Ray, context: &RenderContext) -> Color {
+        match first_hit(ray, context) {
+            Some(hit) => hit.material().albedo(&hit),
+            None => Color::black(),
+        }
+    }
+}
+
+/// One arbitrary color per material ID: surfaces sharing a color share a material, and a
+/// material keeps its color from one render to the next. Materials that weren't numbered are
+/// white.
+pub struct MaterialIdIntegrator;
+
+impl Integrator for MaterialIdIntegrator {
+    fn color(&self, ray: &Ray, context: &RenderContext) -> Color {
+        match first_hit(ray, context) {
+            Some(hit) => match hit.material().id() {
+                0 => Color::white(),
+                id => Color::from_id(id as u64),
+            },
+            None => Color::black(),
+        }
+    }
+}
+
+/// Number of surfaces the path tracer bounces on, from black (none) to white (`WHITE_BOUNCES`
+/// or `max_depth`, whichever is lower), with the same colors as the sample heatmap.
+pub struct BounceIntegrator {
+    path_tracer: PathTracer,
+}
+
+impl BounceIntegrator {
+    /// Path length shown in white. Russian roulette ends most paths long before `max_depth`, so
+    /// scaling to it would leave the whole image dark.
+    pub const WHITE_BOUNCES: u32 = 8;
+
+    pub fn new(path_tracer: PathTracer) -> Self {
+        BounceIntegrator { path_tracer }
+    }
+}
+
+impl Integrator for BounceIntegrator {
+    fn color(&self, ray: &Ray, context: &RenderContext) -> Color {
+        let (_, bounces) = self.path_tracer.trace(ray, context);
+        let white = context.max_depth.clamp(1, BounceIntegrator::WHITE_BOUNCES);
+        let heat = Color::heat(bounces as f64 / white as f64);
+        // the ramp is in display colors, undo the encoding the image gets when saved
+        Color::srgb_to_linear(&heat)
+    }
+}
+
+/// Ambient occlusion: white where a cosine distributed ray from the first hit escapes without
+/// meeting anything closer than `distance`, black where it is blocked. Averaged over the pixel
+/// samples, creases and contact areas get darker.
+pub struct AmbientOcclusionIntegrator {
+    distance: f64,
+}
+
+impl AmbientOcclusionIntegrator {
+    pub fn new(distance: f64) -> Self {
+        AmbientOcclusionIntegrator { distance }
+    }
+}
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn color(&self, ray: &Ray, context: &RenderContext) -> Color {
+        let Some(hit) = first_hit(ray, context) else {
+            return Color::white();
+        };
+
+        let direction = CosinePdf::new(*hit.normal()).generate().normalized();
+        let probe = Ray::with_time(*hit.location(), direction, ray.time());
+        match context.world.hit(&probe, Interval::new(0.001, self.distance)) {
+            Some(_) => Color::black(),
+            None => Color::white(),
+        }
+    }
+}
+
+/// Integrators that can be picked by name on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegratorKind {
+    Path,
+    Normals,
+    Depth,
+    Albedo,
+    MaterialId,
+    Bounces,
+    AmbientOcclusion,
+}
+
+impl IntegratorKind {
+    pub const NAMES: [&'static str; 7] =
+        ["path", "normals", "depth", "albedo", "material-id", "bounces", "ao"];
+}
+
+impl FromStr for IntegratorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(IntegratorKind::Path),
+            "normals" => Ok(IntegratorKind::Normals),
+            "depth" => Ok(IntegratorKind::Depth),
+            "albedo" => Ok(IntegratorKind::Albedo),
+            "material-id" => Ok(IntegratorKind::MaterialId),
+            "bounces" => Ok(IntegratorKind::Bounces),
+            "ao" => Ok(IntegratorKind::AmbientOcclusion),
+            _ => Err(format!(
+                "unknown integrator `{}`, expected one of: {}",
+                s,
+                IntegratorKind::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl Display for IntegratorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            IntegratorKind::Path => "path",
+            IntegratorKind::Normals => "normals",
+            IntegratorKind::Depth => "depth",
+            IntegratorKind::Albedo => "albedo",
+            IntegratorKind::MaterialId => "material-id",
+            IntegratorKind::Bounces => "bounces",
+            IntegratorKind::AmbientOcclusion => "ao",
+        };
+        write!(f, "{}", name)
+    }
+}
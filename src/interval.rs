@@ -1,5 +1,4 @@
-use std::f64::{INFINITY, NEG_INFINITY};
-
+#[derive(Debug, Clone, Copy)]
 pub struct Interval {
     min: f64,
     max: f64,
@@ -10,6 +9,14 @@ impl Interval {
         Interval { min, max }
     }
 
+    /// Smallest interval containing both `a` and `b`.
+    pub fn enclosing(a: &Interval, b: &Interval) -> Self {
+        Interval {
+            min: f64::min(a.min, b.min),
+            max: f64::max(a.max, b.max),
+        }
+    }
+
     pub fn contains(&self, value: f64) -> bool {
         self.min <= value && self.max >= value
     }
@@ -26,6 +33,16 @@ impl Interval {
         }
     }
 
+    #[inline(always)]
+    pub fn size(&self) -> f64 {
+        self.max - self.min
+    }
+
+    pub fn expand(&self, delta: f64) -> Self {
+        let padding = delta * 0.5;
+        Interval::new(self.min - padding, self.max + padding)
+    }
+
     #[inline(always)]
     pub fn min(&self) -> f64 {
         self.min
@@ -39,17 +56,17 @@ impl Interval {
     #[inline(always)]
     pub const fn empty() -> Self {
         Interval {
-            min: INFINITY,
-            max: NEG_INFINITY
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY
         }
     }
 
     #[inline(always)]
     pub const fn universe() -> Self {
         Interval {
-            min: NEG_INFINITY,
-            max: INFINITY
+            min: f64::NEG_INFINITY,
+            max: f64::INFINITY
         }
     }
 
-}
\ No newline at end of file
+}
@@ -1,12 +1,37 @@
-mod camera;
+mod aabb;
+pub mod animation;
+pub mod aov;
+pub mod bvh;
+pub mod camera;
+pub mod checkpoint;
+pub mod cli;
 pub mod color;
+pub mod denoise;
+pub mod environment;
+pub mod framebuffer;
 pub mod hittable;
 pub mod hittable_list;
+pub mod integrator;
 mod interval;
 pub mod material;
+pub mod matrix;
+pub mod medium;
+pub mod microfacet;
+pub mod obj;
+pub mod onb;
+pub mod pdf;
+pub mod perlin;
+pub mod plane;
+pub mod quad;
 mod ray;
 pub mod raytracer;
+pub mod sampling;
+pub mod scene;
 pub mod sphere;
+pub mod texture;
+pub mod tonemap;
+pub mod transform;
+pub mod triangle;
 pub mod utilities;
 mod vec3;
 
@@ -1,20 +1,159 @@
 use raytracing_series::{
+    bvh::BvhNode,
+    camera::Camera,
+    checkpoint,
+    cli::{Arguments, Command, USAGE},
     color::Color,
+    environment::ConstantEnvironment,
     hittable_list::HittableList,
-    material::{Dielectric, Lambertian, Material, Metal},
-    raytracer::{ExecutionMethod, Raytracer, RaytracerOptions},
+    hittable::Hittable,
+    material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, MaterialIds, Metal},
+    matrix::Matrix4,
+    medium::ConstantMedium,
+    raytracer::Raytracer,
+    scene::Scene,
+    plane::Plane,
+    quad::{make_box, Quad},
     sphere::Sphere,
-    utilities::{rand, rand_range},
+    texture::MarbleTexture,
+    transform::Transform,
+    utilities::{self, rand, rand_range},
     Point,
 };
 
-use std::{env, sync::Arc};
+use std::{env, process, sync::Arc};
 
 fn main() {
+    let arguments = match Arguments::parse(env::args().skip(1)) {
+        Ok(Command::Render(arguments)) => arguments,
+        Ok(Command::Help) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\nRun with `--help` to see the available options.", err);
+            process::exit(2);
+        }
+    };
+
+    let checkpoint = arguments.resume().map(|path| {
+        checkpoint::load(path).unwrap_or_else(|err| {
+            eprintln!("error: cannot resume {}: {}", path, err);
+            process::exit(1);
+        })
+    });
+
+    // a resumed render has to regenerate the same scene and noise it was started with
+    let seed = match (&checkpoint, arguments.seed()) {
+        (Some(checkpoint), Some(seed)) if checkpoint.seed != seed => {
+            eprintln!("error: the checkpoint was rendered with seed {}, not {}", checkpoint.seed, seed);
+            process::exit(2);
+        }
+        (Some(checkpoint), _) => checkpoint.seed,
+        (None, seed) => seed.unwrap_or_else(utilities::random_seed),
+    };
+    println!("Seed: {}", seed);
+    utilities::seed(seed);
+
+    let scene = load_scene(arguments.scene()).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    });
+
+    let mut camera = scene.camera;
+    camera.set_seed(seed);
+    if let Some((width, height)) = arguments.image_size(camera.aspect_ratio(), camera.width()) {
+        camera = camera.with_image_size(width, height);
+    }
+    if let Some(samples) = arguments.samples() {
+        camera.set_samples(samples);
+    }
+    if let Some(max_depth) = arguments.max_depth() {
+        camera.set_max_depth(max_depth);
+    }
+    camera.set_integrator(arguments.integrator());
+    camera.set_adaptive_sampling(arguments.adaptive_sampling());
+
+    let mut raytracer = Raytracer::with_camera(camera);
+    raytracer.set_tone_mapping(arguments.tone_mapping());
+    raytracer.set_aovs(arguments.aovs().to_vec());
+    raytracer.set_denoiser(arguments.denoiser());
+    if let Some(checkpoint) = checkpoint {
+        if let Err(err) = raytracer.resume(checkpoint) {
+            eprintln!("error: cannot resume {}: {}", arguments.resume().unwrap_or_default(), err);
+            process::exit(1);
+        }
+    }
+    // numbered so the object ID AOV can tell the objects apart
+    let world = BvhNode::from(scene.world.numbered());
+
+    raytracer.render_progressive(&world, arguments.raytracer_options(), |raytracer| {
+        if let Some(path) = arguments.checkpoint() {
+            if let Err(err) = raytracer.save_checkpoint(path) {
+                eprintln!("warning: cannot save checkpoint {}: {}", path, err);
+            }
+        }
+        if arguments.pass_samples().is_some() {
+            if let Err(err) = save_images(raytracer, &arguments) {
+                eprintln!("warning: {}", err);
+            }
+        }
+    });
+    if let Err(err) = save_images(&raytracer, &arguments) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+    if let Some(heatmap) = arguments.heatmap() {
+        if let Err(err) = raytracer.save_heatmap(heatmap) {
+            eprintln!("error: cannot save {}: {}", heatmap, err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Saves the output image, and the raw one too when denoising with `--keep-raw`.
+fn save_images(raytracer: &Raytracer, arguments: &Arguments) -> Result<(), String> {
+    raytracer
+        .save_image(arguments.output())
+        .map_err(|err| format!("cannot save {}: {}", arguments.output(), err))?;
+    if let Some(raw) = arguments.raw_output() {
+        raytracer
+            .save_raw_image(&raw)
+            .map_err(|err| format!("cannot save {}: {}", raw, err))?;
+    }
+    Ok(())
+}
 
-    let options = parse_arguments(env::args().collect());
+const SCENES: [&str; 5] = [
+    "random-spheres",
+    "bouncing-spheres",
+    "perlin-spheres",
+    "cornell-box",
+    "cornell-smoke",
+];
 
-    let mut raytracer = Raytracer::new(
+fn load_scene(name: &str) -> Result<Scene, String> {
+    match name {
+        "random-spheres" => Ok(random_spheres(false)),
+        "bouncing-spheres" => Ok(random_spheres(true)),
+        "perlin-spheres" => Ok(perlin_spheres()),
+        "cornell-box" => Ok(cornell_box(false)),
+        "cornell-smoke" => Ok(cornell_box(true)),
+        path if path.ends_with(".toml") => {
+            Scene::load(path).map_err(|err| format!("{}: {}", path, err))
+        }
+        _ => Err(format!(
+            "unknown scene `{}`, expected a .toml file or one of: {}",
+            name,
+            SCENES.join(", ")
+        )),
+    }
+}
+
+/// The cover scene of the first book. With `bouncing` the diffuse spheres jump up while the
+/// shutter is open, as in the second book.
+fn random_spheres(bouncing: bool) -> Scene {
+    let mut camera = Camera::new(
         16.0 / 9.0,
         1200,
         100,
@@ -25,16 +164,16 @@ fn main() {
         10.0,
         0.6,
     );
+    if bouncing {
+        camera.set_shutter(0.0, 1.0);
+    }
 
     let mut world = HittableList::new();
+    let mut ids = MaterialIds::new();
 
-    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_mat = ids.number(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
 
-    world.add(Arc::new(Sphere::new(
-        Point::new(0.0, -1000.0, 0.0),
-        1000.0,
-        Arc::clone(&ground_mat) as Arc<dyn Material>,
-    )));
+    world.add(Arc::new(Plane::new(Point::zero(), Point::up(), ground_mat)));
 
     for a in -11..11 {
         for b in -11..11 {
@@ -47,17 +186,22 @@ fn main() {
                 match choose_mat {
                     _ if choose_mat < 0.8 => {
                         let albedo = Color::rand() * Color::rand();
-                        sphere_mat = Arc::new(Lambertian::new(albedo));
-                        world.add(Arc::new(Sphere::new(center, 0.2, sphere_mat)));
+                        sphere_mat = ids.number(Arc::new(Lambertian::new(albedo)));
+                        if bouncing {
+                            let end = center + Point::new(0.0, rand_range(0.0, 0.5), 0.0);
+                            world.add(Arc::new(Sphere::moving(center, end, 0.2, sphere_mat)));
+                        } else {
+                            world.add(Arc::new(Sphere::new(center, 0.2, sphere_mat)));
+                        }
                     }
                     _ if choose_mat < 0.95 => {
                         let albedo = Color::rand_range(0.5, 1.0);
                         let fuzz = rand_range(0.0, 0.5);
-                        sphere_mat = Arc::new(Metal::new(albedo, fuzz));
+                        sphere_mat = ids.number(Arc::new(Metal::new(albedo, fuzz)));
                         world.add(Arc::new(Sphere::new(center, 0.2, sphere_mat)));
                     }
                     _ => {
-                        sphere_mat = Arc::new(Dielectric::new(1.5));
+                        sphere_mat = ids.number(Arc::new(Dielectric::new(1.5)));
                         world.add(Arc::new(Sphere::new(center, 0.2, sphere_mat)));
                     }
                 }
@@ -65,27 +209,105 @@ fn main() {
         }
     }
 
-    let mat1 = Arc::new(Dielectric::new(1.5));
+    let mat1 = ids.number(Arc::new(Dielectric::new(1.5)));
     world.add(Arc::new(Sphere::new(Point::new(0.0, 1.0, 0.0), 1.0, mat1)));
 
-    let mat2 = Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
+    let mat2 = ids.number(Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1))));
     world.add(Arc::new(Sphere::new(Point::new(-4.0, 1.0, 0.0), 1.0, mat2)));
 
-    let mat3 = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+    let mat3 = ids.number(Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0)));
     world.add(Arc::new(Sphere::new(Point::new(4.0, 1.0, 0.0), 1.0, mat3)));
 
+    Scene { camera, world }
+}
+
+/// Two marble spheres from the second book, procedural textures only.
+fn perlin_spheres() -> Scene {
+    let camera = Camera::new(
+        16.0 / 9.0,
+        400,
+        100,
+        50,
+        20.0,
+        Point::new(13.0, 2.0, 3.0),
+        Point::zero(),
+        10.0,
+        0.0,
+    );
+
+    let mut world = HittableList::new();
+    let mut ids = MaterialIds::new();
 
-    raytracer.render(&world, options);
-    raytracer.save_image("test.png");
+    let marble = ids.number(Arc::new(Lambertian::textured(Arc::new(MarbleTexture::new(4.0, 10.0, 0)))));
+    world.add(Arc::new(Plane::new(Point::zero(), Point::up(), Arc::clone(&marble))));
+    world.add(Arc::new(Sphere::new(Point::new(0.0, 2.0, 0.0), 2.0, marble)));
+
+    Scene { camera, world }
 }
 
-fn parse_arguments(args: Vec<String>) -> RaytracerOptions {
-    if args.len() <= 1 {
-        return RaytracerOptions::new(ExecutionMethod::Single);
-    }
+/// The Cornell box of the second book. With `smoke` the two boxes are filled with black and
+/// white smoke instead of being solid.
+fn cornell_box(smoke: bool) -> Scene {
+    let mut camera = Camera::new(
+        1.0,
+        600,
+        200,
+        50,
+        40.0,
+        Point::new(278.0, 278.0, -800.0),
+        Point::new(278.0, 278.0, 0.0),
+        10.0,
+        0.0,
+    );
+    camera.set_background(Arc::new(ConstantEnvironment::new(Color::black())));
+
+    let mut world = HittableList::new();
+    let mut ids = MaterialIds::new();
 
-    match args[1].as_str() {
-        "parallel" => RaytracerOptions::new(ExecutionMethod::Parallel),
-        _ => RaytracerOptions::new(ExecutionMethod::Single)
+    let red = ids.number(Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05))));
+    let white = ids.number(Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73))));
+    let green = ids.number(Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15))));
+    let light = ids.number(Arc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0))));
+
+    let x = Point::new(555.0, 0.0, 0.0);
+    let y = Point::new(0.0, 555.0, 0.0);
+    let z = Point::new(0.0, 0.0, 555.0);
+    world.add(Arc::new(Quad::new(Point::new(555.0, 0.0, 0.0), y, z, green)));
+    world.add(Arc::new(Quad::new(Point::zero(), y, z, red)));
+    let ceiling_light: Arc<dyn Hittable> = Arc::new(Quad::new(
+        Point::new(343.0, 554.0, 332.0),
+        Point::new(-130.0, 0.0, 0.0),
+        Point::new(0.0, 0.0, -105.0),
+        light,
+    ));
+    world.add(Arc::clone(&ceiling_light));
+    // the light is small, sampling it directly instead of waiting for rays to hit it makes
+    // most of the noise go away
+    let mut lights = HittableList::new();
+    lights.add(ceiling_light);
+    camera.set_lights(Arc::new(lights));
+    world.add(Arc::new(Quad::new(Point::zero(), x, z, Arc::clone(&white))));
+    world.add(Arc::new(Quad::new(Point::new(555.0, 555.0, 555.0), -x, -z, Arc::clone(&white))));
+    world.add(Arc::new(Quad::new(Point::new(0.0, 0.0, 555.0), x, y, Arc::clone(&white))));
+
+    // both boxes are modeled at the origin and placed by their transform
+    let tall_box = make_box(Point::zero(), Point::new(165.0, 330.0, 165.0), Arc::clone(&white));
+    let placement = Matrix4::translation(&Point::new(265.0, 0.0, 295.0)) * Matrix4::rotation(&Point::up(), 15.0);
+    let tall_box: Arc<dyn Hittable> = Arc::new(Transform::new(Arc::new(tall_box), placement));
+
+    let short_box = make_box(Point::zero(), Point::new(165.0, 165.0, 165.0), white);
+    let placement = Matrix4::translation(&Point::new(130.0, 0.0, 65.0)) * Matrix4::rotation(&Point::up(), -18.0);
+    let short_box: Arc<dyn Hittable> = Arc::new(Transform::new(Arc::new(short_box), placement));
+
+    if smoke {
+        let black = ids.number(Arc::new(Isotropic::new(Color::black())));
+        let white = ids.number(Arc::new(Isotropic::new(Color::white())));
+        world.add(Arc::new(ConstantMedium::new(tall_box, 0.01, black)));
+        world.add(Arc::new(ConstantMedium::new(short_box, 0.01, white)));
+    } else {
+        world.add(tall_box);
+        world.add(short_box);
     }
-}
\ No newline at end of file
+
+    Scene { camera, world }
+}
@@ -1,4 +1,11 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
 use crate::color::Color;
+use crate::microfacet::{self, Ggx};
+use crate::onb::Onb;
+use crate::pdf::{CosinePdf, Pdf, SpherePdf};
+use crate::texture::{SolidColor, Texture};
 use crate::utilities::rand;
 use crate::vec3::Vec3;
 use crate::{hittable::HitResult, ray::Ray};
@@ -6,16 +13,33 @@ use crate::{hittable::HitResult, ray::Ray};
 pub struct ScatterResult {
     attenuation: Color,
     scattered_ray: Ray,
+    pdf: Option<f64>,
 }
 
 impl ScatterResult {
+    /// Scattering toward `scattered_ray`, weighted by `attenuation`. Without a pdf the direction
+    /// is treated as specular: it is the only one the material could have picked.
     pub fn new(attenuation: Color, scattered_ray: Ray) -> Self {
         ScatterResult {
             attenuation,
             scattered_ray,
+            pdf: None,
         }
     }
 
+    /// Sets the density the scattered direction was drawn with, for materials that spread light
+    /// over many directions and describe it with `bsdf` and `scattering_pdf`.
+    pub fn with_pdf(mut self, pdf: f64) -> Self {
+        self.pdf = Some(pdf);
+        self
+    }
+
+    /// Density of the scattered direction, `None` for specular scattering.
+    #[inline(always)]
+    pub fn pdf(&self) -> Option<f64> {
+        self.pdf
+    }
+
     #[inline(always)]
     pub fn attenuation(&self) -> Color {
         self.attenuation
@@ -26,60 +50,170 @@ impl ScatterResult {
         &self.scattered_ray
     }
 }
-pub trait Material {
+pub trait Material: Send + Sync {
+    /// Samples a scattered ray. Its attenuation is the BSDF times the cosine term divided by the
+    /// density of the direction, so it can be used as is to weight the light coming back.
     fn scatter(&self, ray: &Ray, hit_result: &HitResult) -> Option<ScatterResult>;
+
+    /// BSDF times the cosine term for light leaving toward `direction`, for the materials whose
+    /// scattered rays have a pdf. Lets the integrator weight directions it picked itself, such
+    /// as the ones toward lights.
+    fn bsdf(&self, _ray: &Ray, _hit_result: &HitResult, _direction: &Vec3) -> Color {
+        Color::black()
+    }
+
+    /// Density of `scatter` picking `direction`.
+    fn scattering_pdf(&self, _ray: &Ray, _hit_result: &HitResult, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    /// Base color of the surface at the hit point, for debug views and denoising guides.
+    fn albedo(&self, _hit_result: &HitResult) -> Color {
+        Color::black()
+    }
+
+    /// Light given off by the surface at the hit point, black for anything but light sources.
+    fn emitted(&self, _hit_result: &HitResult) -> Color {
+        Color::black()
+    }
+
+    /// ID given to the material when the scene was loaded, 0 if it wasn't numbered (see
+    /// `MaterialIds`).
+    fn id(&self) -> u32 {
+        0
+    }
+}
+
+/// Numbers the materials of a scene from 1, in the order they are handed over, so they keep
+/// the same IDs from one run to the next.
+#[derive(Debug, Default)]
+pub struct MaterialIds {
+    last: u32,
+}
+
+impl MaterialIds {
+    pub fn new() -> Self {
+        MaterialIds::default()
+    }
+
+    /// `material`, reporting the next ID as its `Material::id`.
+    pub fn number(&mut self, material: Arc<dyn Material>) -> Arc<dyn Material> {
+        self.last += 1;
+        Arc::new(NumberedMaterial { material, id: self.last })
+    }
+}
+
+/// Material handed out by `MaterialIds::number`.
+struct NumberedMaterial {
+    material: Arc<dyn Material>,
+    id: u32,
+}
+
+impl Material for NumberedMaterial {
+    fn scatter(&self, ray: &Ray, hit_result: &HitResult) -> Option<ScatterResult> {
+        self.material.scatter(ray, hit_result)
+    }
+
+    fn bsdf(&self, ray: &Ray, hit_result: &HitResult, direction: &Vec3) -> Color {
+        self.material.bsdf(ray, hit_result, direction)
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_result: &HitResult, direction: &Vec3) -> f64 {
+        self.material.scattering_pdf(ray, hit_result, direction)
+    }
+
+    fn albedo(&self, hit_result: &HitResult) -> Color {
+        self.material.albedo(hit_result)
+    }
+
+    fn emitted(&self, hit_result: &HitResult) -> Color {
+        self.material.emitted(hit_result)
+    }
+
+    fn id(&self) -> u32 {
+        self.id
+    }
 }
 
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
+        Lambertian::textured(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Self {
         Lambertian { albedo }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray: &Ray, hit_result: &HitResult) -> Option<ScatterResult> {
-        let mut scatter_dir = *hit_result.normal() + Vec3::rand_unit();
-        if scatter_dir.near_zero() {
-            scatter_dir = *hit_result.normal();
-        }
+    fn scatter(&self, ray: &Ray, hit_result: &HitResult) -> Option<ScatterResult> {
+        let pdf = CosinePdf::new(*hit_result.normal());
+        let scatter_dir = pdf.generate();
 
-        Some(ScatterResult::new(
-            self.albedo,
-            Ray::new(*hit_result.location(), scatter_dir),
-        ))
+        let (u, v) = hit_result.uv();
+        Some(
+            ScatterResult::new(
+                self.albedo.value(u, v, hit_result.location()),
+                Ray::with_time(*hit_result.location(), scatter_dir, ray.time()),
+            )
+            .with_pdf(pdf.value(&scatter_dir)),
+        )
+    }
+
+    fn bsdf(&self, _ray: &Ray, hit_result: &HitResult, direction: &Vec3) -> Color {
+        let (u, v) = hit_result.uv();
+        let cosine = Vec3::dot(&direction.normalized(), hit_result.normal()).max(0.0);
+        self.albedo.value(u, v, hit_result.location()) * (cosine / PI)
+    }
+
+    fn scattering_pdf(&self, _ray: &Ray, hit_result: &HitResult, direction: &Vec3) -> f64 {
+        CosinePdf::new(*hit_result.normal()).value(direction)
+    }
+
+    fn albedo(&self, hit_result: &HitResult) -> Color {
+        let (u, v) = hit_result.uv();
+        self.albedo.value(u, v, hit_result.location())
     }
 }
 
 pub struct Metal {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: Color, fuzz: f64) -> Self {
+        Metal::textured(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, fuzz: f64) -> Self {
         Metal { albedo, fuzz }
     }
 }
 
 impl Material for Metal {
     fn scatter(&self, ray: &Ray, hit_result: &HitResult) -> Option<ScatterResult> {
-        let reflected = Vec3::reflect(&ray.direction().normalized(), &hit_result.normal());
-        let scattered = Ray::new(
+        let reflected = Vec3::reflect(&ray.direction().normalized(), hit_result.normal());
+        let scattered = Ray::with_time(
             *hit_result.location(),
             reflected + self.fuzz * Vec3::rand_unit(),
+            ray.time(),
         );
         if Vec3::dot(&scattered.direction(), hit_result.normal()) < 0.0 {
             return None;
         }
 
-        Some(ScatterResult {
-            attenuation: self.albedo,
-            scattered_ray: scattered,
-        })
+        let (u, v) = hit_result.uv();
+        Some(ScatterResult::new(self.albedo.value(u, v, hit_result.location()), scattered))
+    }
+
+    fn albedo(&self, hit_result: &HitResult) -> Color {
+        let (u, v) = hit_result.uv();
+        self.albedo.value(u, v, hit_result.location())
     }
 }
 
@@ -120,9 +254,251 @@ impl Material for Dielectric {
             Vec3::refract(&unit_dir, hit_result.normal(), refraction_ratio)
         };
 
-        Some(ScatterResult {
-            attenuation: Color::white(),
-            scattered_ray: Ray::new(*hit_result.location(), direction),
-        })
+        Some(ScatterResult::new(
+            Color::white(),
+            Ray::with_time(*hit_result.location(), direction, ray.time()),
+        ))
+    }
+
+    fn albedo(&self, _hit_result: &HitResult) -> Color {
+        Color::white()
+    }
+}
+
+/// Physically based surface following the glTF metallic-roughness model: a `base_color`,
+/// how `metallic` the surface is and how rough, both between 0 and 1.
+///
+/// Dielectrics (`metallic` 0) are a diffuse base under a clear coat reflecting 4% of the light
+/// at normal incidence, metals (`metallic` 1) reflect only, tinted by the base color; values in
+/// between blend the two. Reflections spread over a GGX microfacet distribution with Fresnel
+/// following Schlick's approximation, and the diffuse base only gets the light the coat lets
+/// through, so the surface never reflects more than it receives. Light bouncing several times
+/// between microfacets isn't accounted for, which darkens very rough metals a little.
+///
+/// Directions are drawn either from the distribution of the visible microfacet normals or with
+/// a cosine density, picked in proportion to the Fresnel reflectance of each lobe.
+pub struct MetallicRoughness {
+    base_color: Arc<dyn Texture>,
+    metallic: f64,
+    roughness: f64,
+    distribution: Ggx,
+    /// Complex index of refraction (`eta`, `k`) replacing the base color of the metal.
+    conductor: Option<(Color, Color)>,
+}
+
+impl MetallicRoughness {
+    /// Reflectance at normal incidence of dielectrics, the one of an index of refraction of 1.5.
+    pub const DIELECTRIC_F0: f64 = 0.04;
+
+    pub fn new(base_color: Color, metallic: f64, roughness: f64) -> Self {
+        MetallicRoughness::textured(Arc::new(SolidColor::new(base_color)), metallic, roughness)
+    }
+
+    pub fn textured(base_color: Arc<dyn Texture>, metallic: f64, roughness: f64) -> Self {
+        let metallic = metallic.clamp(0.0, 1.0);
+        let roughness = roughness.clamp(0.0, 1.0);
+        MetallicRoughness {
+            base_color,
+            metallic,
+            roughness,
+            distribution: Ggx::from_roughness(roughness),
+            conductor: None,
+        }
+    }
+
+    /// Makes the metallic part reflect with the Fresnel equations of a conductor of complex
+    /// index of refraction `eta + i k` (per color channel, e.g. gold is about `eta` =
+    /// [0.18, 0.42, 1.37], `k` = [3.42, 2.35, 1.77]) instead of tinting by the base color.
+    pub fn with_conductor(mut self, eta: Color, k: Color) -> Self {
+        self.conductor = Some((eta, k));
+        self
+    }
+
+    #[inline(always)]
+    pub fn metallic(&self) -> f64 {
+        self.metallic
+    }
+
+    #[inline(always)]
+    pub fn roughness(&self) -> f64 {
+        self.roughness
+    }
+
+    /// Local frame of the hit, direction toward the viewer in it, and base color.
+    fn shading(&self, ray: &Ray, hit_result: &HitResult) -> (Onb, Vec3, Color) {
+        let onb = Onb::new(hit_result.normal());
+        let wo = onb.local(&-ray.direction().normalized());
+        let (u, v) = hit_result.uv();
+        (onb, wo, self.base_color.value(u, v, hit_result.location()))
+    }
+
+    /// Fresnel reflectance of the metal at `cos`.
+    fn metal_fresnel(&self, base_color: Color, cos: f64) -> Color {
+        match self.conductor {
+            Some((eta, k)) => microfacet::conductor(eta, k, cos),
+            None => microfacet::schlick(base_color, cos),
+        }
+    }
+
+    /// Fresnel reflectance of the dielectric coat at `cos`.
+    fn dielectric_fresnel(&self, cos: f64) -> f64 {
+        *microfacet::schlick(MetallicRoughness::DIELECTRIC_F0 * Color::white(), cos).r()
+    }
+
+    /// Probability of sampling the specular lobe rather than the diffuse one, from how much
+    /// light each of them reflects toward `wo`.
+    fn specular_probability(&self, base_color: Color, wo: &Vec3) -> f64 {
+        let diffuse = (1.0 - self.metallic)
+            * (1.0 - self.dielectric_fresnel(*wo.z()))
+            * base_color.luminance();
+        if diffuse <= 0.0 {
+            return 1.0;
+        }
+        let specular = self.metallic * self.metal_fresnel(base_color, *wo.z()).luminance()
+            + (1.0 - self.metallic) * self.dielectric_fresnel(*wo.z());
+        // keep both lobes sampled, either may carry the brightest light
+        (specular / (specular + diffuse)).clamp(0.1, 0.9)
+    }
+
+    /// BSDF times the cosine term, in the local frame.
+    fn eval(&self, base_color: Color, wo: &Vec3, wi: &Vec3) -> Color {
+        if *wo.z() <= 0.0 || *wi.z() <= 0.0 {
+            return Color::black();
+        }
+        let h = (*wo + *wi).normalized();
+        let cos_h = Vec3::dot(wo, &h);
+        // D G F / (4 cos_o cos_i), times cos_i
+        let specular = self.distribution.d(&h) * self.distribution.g2(wo, wi) / (4.0 * wo.z());
+
+        let metal = specular * self.metal_fresnel(base_color, cos_h);
+        let fresnel = self.dielectric_fresnel(cos_h);
+        let dielectric = specular * fresnel * Color::white() + (1.0 - fresnel) * (wi.z() / PI) * base_color;
+        self.metallic * metal + (1.0 - self.metallic) * dielectric
+    }
+
+    /// Density of `scatter` picking `wi`, in the local frame.
+    fn pdf(&self, base_color: Color, wo: &Vec3, wi: &Vec3) -> f64 {
+        if *wo.z() <= 0.0 || *wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let h = (*wo + *wi).normalized();
+        let cos_h = Vec3::dot(wo, &h);
+        let specular = if cos_h > 0.0 {
+            // reflecting maps the density of the normal onto the directions
+            self.distribution.visible_normal_pdf(wo, &h) / (4.0 * cos_h)
+        } else {
+            0.0
+        };
+        let diffuse = wi.z() / PI;
+
+        let p = self.specular_probability(base_color, wo);
+        p * specular + (1.0 - p) * diffuse
+    }
+}
+
+impl Material for MetallicRoughness {
+    fn scatter(&self, ray: &Ray, hit_result: &HitResult) -> Option<ScatterResult> {
+        let (onb, wo, base_color) = self.shading(ray, hit_result);
+        if *wo.z() <= 0.0 {
+            return None;
+        }
+
+        let wi = if rand() < self.specular_probability(base_color, &wo) {
+            let h = self.distribution.sample_visible_normal(&wo);
+            Vec3::reflect(&-wo, &h)
+        } else {
+            onb.local(&CosinePdf::new(*onb.w()).generate().normalized())
+        };
+
+        let pdf = self.pdf(base_color, &wo, &wi);
+        // directions reflected below the surface carry nothing
+        let attenuation = if pdf > 0.0 {
+            self.eval(base_color, &wo, &wi) / pdf
+        } else {
+            Color::black()
+        };
+        let scattered = Ray::with_time(*hit_result.location(), onb.transform(&wi), ray.time());
+        Some(ScatterResult::new(attenuation, scattered).with_pdf(pdf))
+    }
+
+    fn bsdf(&self, ray: &Ray, hit_result: &HitResult, direction: &Vec3) -> Color {
+        let (onb, wo, base_color) = self.shading(ray, hit_result);
+        self.eval(base_color, &wo, &onb.local(&direction.normalized()))
+    }
+
+    fn scattering_pdf(&self, ray: &Ray, hit_result: &HitResult, direction: &Vec3) -> f64 {
+        let (onb, wo, base_color) = self.shading(ray, hit_result);
+        self.pdf(base_color, &wo, &onb.local(&direction.normalized()))
+    }
+
+    fn albedo(&self, hit_result: &HitResult) -> Color {
+        let (u, v) = hit_result.uv();
+        self.base_color.value(u, v, hit_result.location())
+    }
+}
+
+/// Phase function of a participating medium: scatters light evenly in every direction.
+pub struct Isotropic {
+    albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Isotropic::textured(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Self {
+        Isotropic { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, ray: &Ray, hit_result: &HitResult) -> Option<ScatterResult> {
+        let direction = SpherePdf.generate();
+
+        let (u, v) = hit_result.uv();
+        Some(
+            ScatterResult::new(
+                self.albedo.value(u, v, hit_result.location()),
+                Ray::with_time(*hit_result.location(), direction, ray.time()),
+            )
+            .with_pdf(SpherePdf.value(&direction)),
+        )
+    }
+
+    fn bsdf(&self, _ray: &Ray, hit_result: &HitResult, direction: &Vec3) -> Color {
+        let (u, v) = hit_result.uv();
+        self.albedo.value(u, v, hit_result.location()) * SpherePdf.value(direction)
+    }
+
+    fn scattering_pdf(&self, _ray: &Ray, _hit_result: &HitResult, direction: &Vec3) -> f64 {
+        SpherePdf.value(direction)
+    }
+
+    fn albedo(&self, hit_result: &HitResult) -> Color {
+        let (u, v) = hit_result.uv();
+        self.albedo.value(u, v, hit_result.location())
+    }
+}
+
+/// Area light: emits `emit` from both sides of the surface and absorbs everything it receives.
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        DiffuseLight { emit }
     }
 }
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: &Ray, _hit_result: &HitResult) -> Option<ScatterResult> {
+        None
+    }
+
+    fn emitted(&self, _hit_result: &HitResult) -> Color {
+        self.emit
+    }
+}
+
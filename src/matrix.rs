@@ -0,0 +1,167 @@
+use std::ops::Mul;
+
+use super::utilities::deg_to_rad;
+use super::vec3::Vec3;
+use super::Point;
+
+/// Row-major 4x4 matrix of an affine transform, applied to column vectors:
+/// `(a * b).transform_point(p)` applies `b` first, then `a`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4 {
+    m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Matrix4 { m }
+    }
+
+    pub fn identity() -> Self {
+        Matrix4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translation(offset: &Vec3) -> Self {
+        let mut matrix = Matrix4::identity();
+        matrix.m[0][3] = *offset.x();
+        matrix.m[1][3] = *offset.y();
+        matrix.m[2][3] = *offset.z();
+        matrix
+    }
+
+    /// Scales by a different factor along each axis.
+    pub fn scaling(factors: &Vec3) -> Self {
+        let mut matrix = Matrix4::identity();
+        matrix.m[0][0] = *factors.x();
+        matrix.m[1][1] = *factors.y();
+        matrix.m[2][2] = *factors.z();
+        matrix
+    }
+
+    /// Counterclockwise rotation of `degrees` around `axis`, looking down the axis toward
+    /// the origin.
+    pub fn rotation(axis: &Vec3, degrees: f64) -> Self {
+        let a = axis.normalized();
+        let (x, y, z) = (*a.x(), *a.y(), *a.z());
+        let (sin, cos) = deg_to_rad(degrees).sin_cos();
+        let t = 1.0 - cos;
+
+        Matrix4::new([
+            [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y, 0.0],
+            [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x, 0.0],
+            [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    #[inline(always)]
+    pub fn get(&self, row: usize, column: usize) -> f64 {
+        self.m[row][column]
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut result = *self;
+        for (i, row) in result.m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        result
+    }
+
+    /// Inverse matrix, `None` if the matrix is singular (e.g. a scaling by 0).
+    pub fn inverse(&self) -> Option<Self> {
+        // Gauss-Jordan elimination with partial pivoting
+        let mut a = self.m;
+        let mut inverse = Matrix4::identity().m;
+
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))
+                .unwrap();
+            if a[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = 1.0 / a[column][column];
+            for k in 0..4 {
+                a[column][k] *= scale;
+                inverse[column][k] *= scale;
+            }
+            for row in 0..4 {
+                if row != column {
+                    let factor = a[row][column];
+                    for k in 0..4 {
+                        a[row][k] -= factor * a[column][k];
+                        inverse[row][k] -= factor * inverse[column][k];
+                    }
+                }
+            }
+        }
+
+        Some(Matrix4::new(inverse))
+    }
+
+    /// Applies the whole transform, translation included.
+    pub fn transform_point(&self, point: &Point) -> Point {
+        self.transform_vector(point) + Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    /// Applies the linear part only, for directions and offsets.
+    pub fn transform_vector(&self, vector: &Vec3) -> Vec3 {
+        let row = |r: &[f64; 4]| r[0] * vector.x() + r[1] * vector.y() + r[2] * vector.z();
+        Vec3::new(row(&self.m[0]), row(&self.m[1]), row(&self.m[2]))
+    }
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Matrix4::identity()
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut result = [[0.0; 4]; 4];
+        for (i, row) in result.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Matrix4::new(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        let m = Matrix4::translation(&Vec3::new(3.0, -2.0, 5.0))
+            * Matrix4::rotation(&Vec3::new(1.0, 2.0, -0.5), 37.0)
+            * Matrix4::scaling(&Vec3::new(2.0, 0.5, 3.0));
+        let inverse = m.inverse().unwrap();
+        for product in [m * inverse, inverse * m] {
+            for row in 0..4 {
+                for column in 0..4 {
+                    let expected = if row == column { 1.0 } else { 0.0 };
+                    assert!((product.get(row, column) - expected).abs() < 1e-12, "{:?}", product);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert!(Matrix4::scaling(&Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+}
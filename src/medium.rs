@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hittable::{HitResult, Hittable};
+use super::interval::Interval;
+use super::material::Material;
+use super::ray::Ray;
+use super::utilities::rand;
+use super::vec3::Vec3;
+
+/// Volume of constant density (fog, smoke) filling a convex `boundary`.
+///
+/// A ray crossing the volume scatters after a random, exponentially distributed distance, so
+/// thin media let most rays through while dense ones look almost solid. The scattering is up
+/// to the `phase_function` material, usually [`Isotropic`](super::material::Isotropic).
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Arc<dyn Hittable>, density: f64, phase_function: Arc<dyn Material>) -> Self {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitResult> {
+        // where the ray line enters and leaves the boundary, even behind the origin so rays
+        // starting inside the volume work too
+        let entry = self.boundary.hit(ray, Interval::universe())?;
+        let exit = self
+            .boundary
+            .hit(ray, Interval::new(entry.t() + 0.0001, f64::INFINITY))?;
+
+        let t_entry = entry.t().max(interval.min()).max(0.0);
+        let t_exit = exit.t().min(interval.max());
+        if t_entry >= t_exit {
+            return None;
+        }
+
+        let ray_length = ray.direction().len();
+        let distance_inside = (t_exit - t_entry) * ray_length;
+        let hit_distance = self.neg_inv_density * rand().ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t_entry + hit_distance / ray_length;
+        // a point in a volume has no surface normal, any direction will do
+        Some(HitResult::new(
+            ray,
+            ray.at(t),
+            Vec3::right(),
+            Arc::clone(&self.phase_function),
+            t,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
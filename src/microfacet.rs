@@ -0,0 +1,170 @@
+use std::f64::consts::PI;
+
+use super::color::Color;
+use super::utilities::rand;
+use super::vec3::Vec3;
+
+/// Smallest `alpha` of a distribution, smoother surfaces are as good as mirrors and would only
+/// overflow the densities.
+const MIN_ALPHA: f64 = 1e-3;
+
+/// Trowbridge-Reitz (GGX) distribution of the microfacet normals of a rough surface.
+///
+/// Directions are in the local frame of the surface, with the macroscopic normal along z
+/// (see `Onb`), and point away from it.
+#[derive(Debug, Clone, Copy)]
+pub struct Ggx {
+    alpha: f64,
+}
+
+impl Ggx {
+    pub fn new(alpha: f64) -> Self {
+        Ggx {
+            alpha: alpha.max(MIN_ALPHA),
+        }
+    }
+
+    /// Distribution of a perceptual `roughness` between 0 and 1, squared into `alpha` as glTF
+    /// and most authoring tools do.
+    pub fn from_roughness(roughness: f64) -> Self {
+        Ggx::new(roughness * roughness)
+    }
+
+    #[inline(always)]
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    /// Density of microfacets facing `h`, per unit of solid angle and projected area.
+    pub fn d(&self, h: &Vec3) -> f64 {
+        let cos2 = h.z() * h.z();
+        if *h.z() <= 0.0 {
+            return 0.0;
+        }
+        let alpha2 = self.alpha * self.alpha;
+        let t = cos2 * (alpha2 - 1.0) + 1.0;
+        alpha2 / (PI * t * t)
+    }
+
+    /// Smith's auxiliary function, the microfacet area hidden from `w` relative to the visible
+    /// one.
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let cos2 = w.z() * w.z();
+        if cos2 == 0.0 {
+            return f64::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        0.5 * ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0)
+    }
+
+    /// Fraction of the microfacets facing `h` that are visible from `w`.
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Fraction of the microfacets visible from both directions, with the height correlated
+    /// Smith model.
+    pub fn g2(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Draws a microfacet normal among the ones visible from `wo`, so directions reflected off
+    /// it rarely go below the surface (Heitz, "Sampling the GGX Distribution of Visible
+    /// Normals", 2018).
+    pub fn sample_visible_normal(&self, wo: &Vec3) -> Vec3 {
+        // stretch the view so the distribution becomes the hemisphere of alpha 1
+        let view = Vec3::new(self.alpha * wo.x(), self.alpha * wo.y(), *wo.z()).normalized();
+        let len2 = view.x() * view.x() + view.y() * view.y();
+        let t1 = if len2 > 0.0 {
+            Vec3::new(-view.y(), *view.x(), 0.0) / len2.sqrt()
+        } else {
+            Vec3::right()
+        };
+        let t2 = Vec3::cross(&view, &t1);
+
+        // uniform point on the projected disk, squeezed onto its visible part
+        let r = rand().sqrt();
+        let phi = 2.0 * PI * rand();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + view.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let normal = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * view;
+
+        Vec3::new(self.alpha * normal.x(), self.alpha * normal.y(), normal.z().max(0.0)).normalized()
+    }
+
+    /// Density of `sample_visible_normal` returning `h`.
+    pub fn visible_normal_pdf(&self, wo: &Vec3, h: &Vec3) -> f64 {
+        if *wo.z() <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * Vec3::dot(wo, h).max(0.0) * self.d(h) / wo.z()
+    }
+}
+
+/// Schlick's approximation of the Fresnel reflectance, `f0` being the reflectance at normal
+/// incidence and `cos` the cosine between the light and the microfacet normal.
+pub fn schlick(f0: Color, cos: f64) -> Color {
+    let weight = (1.0 - cos.clamp(0.0, 1.0)).powi(5);
+    f0 + weight * (Color::white() - f0)
+}
+
+/// Fresnel reflectance of a conductor of complex index of refraction `eta + i k`, per color
+/// channel, averaged over both polarizations.
+pub fn conductor(eta: Color, k: Color, cos: f64) -> Color {
+    let cos = cos.clamp(0.0, 1.0);
+    let cos2 = cos * cos;
+    let reflectance = |eta: f64, k: f64| {
+        let magnitude2 = eta * eta + k * k;
+        let two_eta_cos = 2.0 * eta * cos;
+        let perpendicular = (magnitude2 - two_eta_cos + cos2) / (magnitude2 + two_eta_cos + cos2);
+        let parallel = (magnitude2 * cos2 - two_eta_cos + 1.0) / (magnitude2 * cos2 + two_eta_cos + 1.0);
+        0.5 * (perpendicular + parallel)
+    };
+    Color::new(
+        reflectance(*eta.r(), *k.r()),
+        reflectance(*eta.g(), *k.g()),
+        reflectance(*eta.b(), *k.b()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utilities::seed;
+
+    /// White furnace: a perfectly reflecting surface lit evenly from everywhere. The weight of
+    /// every sampled direction, BSDF times cosine over density, can't be above 1 without the
+    /// surface reflecting more light than it receives.
+    #[test]
+    fn visible_normal_sampling_gains_no_energy() {
+        seed(5);
+        for roughness in [0.05, 0.2, 0.5, 0.8, 1.0] {
+            let ggx = Ggx::from_roughness(roughness);
+            let mut total = 0.0;
+            let count = 2000;
+            for n in 0..count {
+                // viewing angles from grazing to head on
+                let cos = (n as f64 + 0.5) / count as f64;
+                let phi = 2.0 * PI * rand();
+                let sin = (1.0 - cos * cos).sqrt();
+                let wo = Vec3::new(sin * phi.cos(), sin * phi.sin(), cos);
+
+                let h = ggx.sample_visible_normal(&wo);
+                let wi = Vec3::reflect(&-wo, &h);
+                if *wi.z() <= 0.0 {
+                    continue;
+                }
+                let bsdf_cos = ggx.d(&h) * ggx.g2(&wo, &wi) / (4.0 * wo.z());
+                let pdf = ggx.visible_normal_pdf(&wo, &h) / (4.0 * Vec3::dot(&wo, &h));
+                let weight = bsdf_cos / pdf;
+                assert!(weight <= 1.0 + 1e-9, "roughness {}: weight {} for {:?}", roughness, weight, wo);
+                total += weight;
+            }
+            // single scattering loses the light the microfacets shadow, up to half of it for
+            // the roughest surfaces
+            let average = total / count as f64;
+            assert!(average > 0.4, "roughness {}: average weight {}", roughness, average);
+        }
+    }
+}
@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::color::Color;
+use super::hittable_list::HittableList;
+use super::material::{Dielectric, Lambertian, Material, MaterialIds, Metal, MetallicRoughness};
+use super::texture::{ImageTexture, SolidColor, Texture};
+use super::triangle::TriangleMesh;
+use super::vec3::Vec3;
+use super::Point;
+
+/// Loads a Wavefront OBJ file into a list of `TriangleMesh`es, one per group and material.
+///
+/// Materials come from the `mtllib` files referenced by the OBJ (see `load_mtl` for how they
+/// are mapped) and are numbered by `material_ids` in the order of their names, library by
+/// library; faces before any `usemtl` statement use `default_material`.
+/// Polygons are triangulated as fans, and normals and texture coordinates are kept only for
+/// meshes where every vertex has them.
+pub fn load_obj<P: AsRef<Path>>(
+    path: P,
+    default_material: Arc<dyn Material>,
+    material_ids: &mut MaterialIds,
+) -> Result<HittableList, ObjError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|err| ObjError::io(path, err))?;
+    parse_obj(&source, path, default_material, material_ids)
+}
+
+/// `load_obj` on the `source` of the OBJ file at `path`.
+fn parse_obj(
+    source: &str,
+    path: &Path,
+    default_material: Arc<dyn Material>,
+    material_ids: &mut MaterialIds,
+) -> Result<HittableList, ObjError> {
+    let mut positions: Vec<Point> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut uvs: Vec<(f64, f64)> = vec![];
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+
+    let mut meshes = HittableList::new();
+    let mut builder = MeshBuilder::new(Arc::clone(&default_material));
+
+    for (number, line) in source.lines().enumerate() {
+        let number = number + 1;
+        let error = |message: String| ObjError::new(path, Some(number), message);
+
+        let mut tokens = line.split('#').next().unwrap_or("").split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => positions.push(parse_vec3(&args, 3..=4).map_err(error)?),
+            "vn" => normals.push(parse_vec3(&args, 3..=3).map_err(error)?),
+            "vt" => {
+                let uv = parse_floats(&args, 1..=3).map_err(error)?;
+                uvs.push((uv[0], uv.get(1).copied().unwrap_or(0.0)));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(error(format!(
+                        "face needs at least 3 vertices, found {}",
+                        args.len()
+                    )));
+                }
+                let face = args
+                    .iter()
+                    .map(|vertex| {
+                        parse_face_vertex(vertex, positions.len(), uvs.len(), normals.len())
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+                builder.add_face(&face, &positions, &uvs, &normals);
+            }
+            "g" | "o" => {
+                builder.finish_into(&mut meshes);
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                let material = materials
+                    .get(&name)
+                    .ok_or_else(|| error(format!("unknown material `{}`", name)))?;
+                builder.finish_into(&mut meshes);
+                builder.material = Arc::clone(material);
+            }
+            "mtllib" => {
+                let dir = path.parent().unwrap_or(Path::new(""));
+                for library in &args {
+                    let mut library: Vec<_> = load_mtl(dir.join(library))?.into_iter().collect();
+                    library.sort_by(|(a, _), (b, _)| a.cmp(b));
+                    for (name, material) in library {
+                        materials.insert(name, material_ids.number(material));
+                    }
+                }
+            }
+            // smoothing groups, lines, points and the like don't affect the triangles
+            _ => (),
+        }
+    }
+    builder.finish_into(&mut meshes);
+
+    Ok(meshes)
+}
+
+/// Loads the materials of a Wavefront MTL file, mapped onto the renderer's materials:
+///
+/// * `d` < 1 (or `Tr` > 0): `Dielectric` with the `Ni` index of refraction (1.5 if missing)
+/// * `Pr` or `Pm` (the PBR extension): `MetallicRoughness` with `Kd` base color, `Pm` metallic
+///   and `Pr` roughness, 0 and 1 when missing
+/// * `Ks` brighter than `Kd`, or `illum 3`: `Metal` with `Ks` albedo and fuzz derived from the
+///   `Ns` specular exponent
+/// * otherwise: `Lambertian` with `Kd` albedo
+pub fn load_mtl<P: AsRef<Path>>(path: P) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|err| ObjError::io(path, err))?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlDesc)> = None;
+
+    for (number, line) in source.lines().enumerate() {
+        let number = number + 1;
+        let error = |message: String| ObjError::new(path, Some(number), message);
+
+        let mut tokens = line.split('#').next().unwrap_or("").split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(error(String::from("`newmtl` needs a name")));
+            }
+            if let Some((name, desc)) = current.take() {
+                materials.insert(name, desc.build());
+            }
+            current = Some((args.join(" "), MtlDesc::default()));
+            continue;
+        }
+
+        let Some((_, desc)) = current.as_mut() else {
+            return Err(error(format!("`{}` before any `newmtl`", keyword)));
+        };
+        match keyword {
+            "Kd" => desc.diffuse = parse_vec3(&args, 3..=3).map_err(error)?,
+            "Ks" => desc.specular = parse_vec3(&args, 3..=3).map_err(error)?,
+            "Ns" => desc.shininess = parse_floats(&args, 1..=1).map_err(error)?[0],
+            "Ni" => desc.refraction_index = parse_floats(&args, 1..=1).map_err(error)?[0],
+            "d" => desc.dissolve = parse_floats(&args, 1..=1).map_err(error)?[0],
+            "Tr" => desc.dissolve = 1.0 - parse_floats(&args, 1..=1).map_err(error)?[0],
+            "Pr" => desc.roughness = Some(parse_floats(&args, 1..=1).map_err(error)?[0]),
+            "Pm" => desc.metallic = Some(parse_floats(&args, 1..=1).map_err(error)?[0]),
+            "illum" => desc.illum = parse_floats(&args, 1..=1).map_err(error)?[0] as i32,
+            "map_Kd" | "map_Ks" => {
+                // options such as `-s 1 1 1` come first, the file name is last
+                let Some(file) = args.last() else {
+                    return Err(error(format!("`{}` needs a file name", keyword)));
+                };
+                let texture: Arc<dyn Texture> = Arc::new(
+                    ImageTexture::load(base_dir.join(file))
+                        .map_err(|err| error(format!("cannot load texture `{}`: {}", file, err)))?,
+                );
+                if keyword == "map_Kd" {
+                    desc.diffuse_map = Some(texture);
+                } else {
+                    desc.specular_map = Some(texture);
+                }
+            }
+            // bump maps, ambient color and such have no equivalent yet
+            _ => (),
+        }
+    }
+    if let Some((name, desc)) = current.take() {
+        materials.insert(name, desc.build());
+    }
+
+    Ok(materials)
+}
+
+#[derive(Debug)]
+pub struct ObjError {
+    path: PathBuf,
+    line: Option<usize>,
+    message: String,
+}
+
+impl ObjError {
+    fn new(path: &Path, line: Option<usize>, message: String) -> Self {
+        ObjError {
+            path: path.to_path_buf(),
+            line,
+            message,
+        }
+    }
+
+    fn io(path: &Path, err: std::io::Error) -> Self {
+        ObjError::new(path, None, err.to_string())
+    }
+
+    /// 1-based line of the file the error refers to, if known.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+}
+
+impl Display for ObjError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path.display(), line, self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl Error for ObjError {}
+
+/// Indices into the OBJ vertex arrays, already resolved to 0-based.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct FaceVertex {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// Collects the faces of the current group/material into mesh-local vertex buffers.
+struct MeshBuilder {
+    material: Arc<dyn Material>,
+    vertices: HashMap<FaceVertex, usize>,
+    positions: Vec<Point>,
+    normals: Vec<Option<Vec3>>,
+    uvs: Vec<Option<(f64, f64)>>,
+    indices: Vec<[usize; 3]>,
+}
+
+impl MeshBuilder {
+    fn new(material: Arc<dyn Material>) -> Self {
+        MeshBuilder {
+            material,
+            vertices: HashMap::new(),
+            positions: vec![],
+            normals: vec![],
+            uvs: vec![],
+            indices: vec![],
+        }
+    }
+
+    fn add_face(
+        &mut self,
+        face: &[FaceVertex],
+        positions: &[Point],
+        uvs: &[(f64, f64)],
+        normals: &[Vec3],
+    ) {
+        let local: Vec<usize> = face
+            .iter()
+            .map(|vertex| {
+                *self.vertices.entry(*vertex).or_insert_with(|| {
+                    self.positions.push(positions[vertex.position]);
+                    self.uvs.push(vertex.uv.map(|i| uvs[i]));
+                    self.normals.push(vertex.normal.map(|i| normals[i]));
+                    self.positions.len() - 1
+                })
+            })
+            .collect();
+
+        for i in 1..local.len() - 1 {
+            self.indices.push([local[0], local[i], local[i + 1]]);
+        }
+    }
+
+    /// Moves the faces collected so far into a new mesh, if there are any.
+    fn finish_into(&mut self, meshes: &mut HittableList) {
+        if self.indices.is_empty() {
+            return;
+        }
+        let normals = std::mem::take(&mut self.normals).into_iter().collect();
+        let uvs = std::mem::take(&mut self.uvs).into_iter().collect();
+        meshes.add(Arc::new(TriangleMesh::new(
+            std::mem::take(&mut self.positions),
+            normals,
+            uvs,
+            std::mem::take(&mut self.indices),
+            Arc::clone(&self.material),
+        )));
+        self.vertices.clear();
+    }
+}
+
+/// Parses one `v`, `v/vt`, `v//vn` or `v/vt/vn` face entry. Negative indices count back from
+/// the last vertex read so far.
+fn parse_face_vertex(
+    vertex: &str,
+    positions: usize,
+    uvs: usize,
+    normals: usize,
+) -> Result<FaceVertex, String> {
+    let mut parts = vertex.split('/');
+    let resolve = |part: Option<&str>, count: usize, what: &str| -> Result<Option<usize>, String> {
+        match part {
+            None | Some("") => Ok(None),
+            Some(index) => {
+                let index: i64 = index
+                    .parse()
+                    .map_err(|_| format!("invalid {} index `{}`", what, index))?;
+                let resolved = if index < 0 {
+                    count as i64 + index
+                } else {
+                    index - 1
+                };
+                if resolved < 0 || resolved >= count as i64 {
+                    return Err(format!(
+                        "{} index {} out of range ({} defined)",
+                        what, index, count
+                    ));
+                }
+                Ok(Some(resolved as usize))
+            }
+        }
+    };
+
+    let position = resolve(parts.next(), positions, "vertex")?
+        .ok_or_else(|| format!("face vertex `{}` has no position", vertex))?;
+    let uv = resolve(parts.next(), uvs, "texture coordinate")?;
+    let normal = resolve(parts.next(), normals, "normal")?;
+    if parts.next().is_some() {
+        return Err(format!("invalid face vertex `{}`", vertex));
+    }
+
+    Ok(FaceVertex {
+        position,
+        uv,
+        normal,
+    })
+}
+
+fn parse_floats(
+    args: &[&str],
+    count: std::ops::RangeInclusive<usize>,
+) -> Result<Vec<f64>, String> {
+    if !count.contains(&args.len()) {
+        return Err(if count.start() == count.end() {
+            format!("expected {} values, found {}", count.start(), args.len())
+        } else {
+            format!(
+                "expected {} to {} values, found {}",
+                count.start(),
+                count.end(),
+                args.len()
+            )
+        });
+    }
+    args.iter()
+        .map(|arg| {
+            arg.parse::<f64>()
+                .map_err(|_| format!("invalid number `{}`", arg))
+        })
+        .collect()
+}
+
+fn parse_vec3(args: &[&str], count: std::ops::RangeInclusive<usize>) -> Result<Vec3, String> {
+    let values = parse_floats(args, count)?;
+    Ok(Vec3::new(values[0], values[1], values[2]))
+}
+
+struct MtlDesc {
+    diffuse: Color,
+    diffuse_map: Option<Arc<dyn Texture>>,
+    specular: Color,
+    specular_map: Option<Arc<dyn Texture>>,
+    shininess: f64,
+    refraction_index: f64,
+    dissolve: f64,
+    illum: i32,
+    roughness: Option<f64>,
+    metallic: Option<f64>,
+}
+
+impl Default for MtlDesc {
+    fn default() -> Self {
+        MtlDesc {
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            diffuse_map: None,
+            specular: Color::black(),
+            specular_map: None,
+            shininess: 0.0,
+            refraction_index: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+            roughness: None,
+            metallic: None,
+        }
+    }
+}
+
+impl MtlDesc {
+    fn build(&self) -> Arc<dyn Material> {
+        let brightness = |c: &Color| c.r() + c.g() + c.b();
+        // a map replaces the color, as most exporters write the color alongside as a preview
+        let texture = |map: &Option<Arc<dyn Texture>>, color: Color| {
+            map.clone()
+                .unwrap_or_else(|| Arc::new(SolidColor::new(color)) as Arc<dyn Texture>)
+        };
+
+        if self.dissolve < 1.0 {
+            Arc::new(Dielectric::new(self.refraction_index))
+        } else if self.roughness.is_some() || self.metallic.is_some() {
+            Arc::new(MetallicRoughness::textured(
+                texture(&self.diffuse_map, self.diffuse),
+                self.metallic.unwrap_or(0.0),
+                self.roughness.unwrap_or(1.0),
+            ))
+        } else if self.illum == 3 || brightness(&self.specular) > brightness(&self.diffuse) {
+            // Phong exponent to roughness, the usual sqrt(2 / (Ns + 2)) conversion
+            let fuzz = f64::sqrt(2.0 / (self.shininess.max(0.0) + 2.0));
+            Arc::new(Metal::textured(texture(&self.specular_map, self.specular), fuzz.min(1.0)))
+        } else {
+            Arc::new(Lambertian::textured(texture(&self.diffuse_map, self.diffuse)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{HitResult, Hittable};
+    use crate::interval::Interval;
+    use crate::ray::Ray;
+
+    /// Unit square in the z = 0 plane, the face line is appended.
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0.6 0.8\n";
+
+    fn parse(face: &str) -> Result<HittableList, ObjError> {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        parse_obj(&format!("{}{}\n", SQUARE, face), Path::new("test.obj"), material, &mut MaterialIds::new())
+    }
+
+    /// Hits of rays shot down at `(x, y)` of the square.
+    fn hit(meshes: &HittableList, x: f64, y: f64) -> Option<HitResult> {
+        let ray = Ray::new(Point::new(x, y, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        meshes.hit(&ray, Interval::new(0.001, f64::INFINITY))
+    }
+
+    #[test]
+    fn quad_is_split_in_two_triangles() {
+        let meshes = parse("f 1 2 3 4").unwrap();
+        assert!(hit(&meshes, 0.75, 0.25).is_some());
+        assert!(hit(&meshes, 0.25, 0.75).is_some());
+        assert!(hit(&meshes, 1.25, 0.5).is_none());
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_vertex() {
+        let meshes = parse("f -4 -3 -2 -1").unwrap();
+        assert!(hit(&meshes, 0.75, 0.25).is_some());
+        assert!(hit(&meshes, 0.25, 0.75).is_some());
+    }
+
+    #[test]
+    fn normals_without_texture_coordinates() {
+        let meshes = parse("f 1//1 2//1 3//1 4//-1").unwrap();
+        let normal = *hit(&meshes, 0.25, 0.75).unwrap().normal();
+        assert!((normal - Vec3::new(0.0, 0.6, 0.8)).len() < 1e-9, "{:?}", normal);
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected() {
+        for face in ["f 1 2 5", "f 1 2 -5", "f 1 2 0", "f 1//2 2//1 3//1"] {
+            let err = parse(face).err().unwrap_or_else(|| panic!("`{}` was accepted", face));
+            assert_eq!(err.line(), Some(6), "{}", err);
+            assert!(err.to_string().contains("out of range"), "{}", err);
+        }
+    }
+}
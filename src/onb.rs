@@ -0,0 +1,53 @@
+use super::vec3::Vec3;
+
+/// Orthonormal basis built around a direction `w`, to work in the local frame of a surface.
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    pub fn new(w: &Vec3) -> Self {
+        let w = w.normalized();
+        // any vector not parallel to w gives the other two axes
+        let a = if w.x().abs() > 0.9 {
+            Vec3::up()
+        } else {
+            Vec3::right()
+        };
+        let v = Vec3::cross(&w, &a).normalized();
+        let u = Vec3::cross(&w, &v);
+        Onb { u, v, w }
+    }
+
+    #[inline(always)]
+    pub fn u(&self) -> &Vec3 {
+        &self.u
+    }
+
+    #[inline(always)]
+    pub fn v(&self) -> &Vec3 {
+        &self.v
+    }
+
+    #[inline(always)]
+    pub fn w(&self) -> &Vec3 {
+        &self.w
+    }
+
+    /// World space vector of the local coordinates `local`.
+    pub fn transform(&self, local: &Vec3) -> Vec3 {
+        *local.x() * self.u + *local.y() * self.v + *local.z() * self.w
+    }
+
+    /// Local coordinates of the world space vector `world`, reverses `transform`.
+    pub fn local(&self, world: &Vec3) -> Vec3 {
+        Vec3::new(
+            Vec3::dot(world, &self.u),
+            Vec3::dot(world, &self.v),
+            Vec3::dot(world, &self.w),
+        )
+    }
+}
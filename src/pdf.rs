@@ -0,0 +1,101 @@
+use std::f64::consts::PI;
+
+use super::hittable::Hittable;
+use super::utilities::rand;
+use super::vec3::Vec3;
+use super::Point;
+
+/// Probability density over directions, with a way to draw directions from it.
+pub trait Pdf {
+    /// Density, over solid angle, of `generate` returning `direction`.
+    fn value(&self, direction: &Vec3) -> f64;
+
+    fn generate(&self) -> Vec3;
+}
+
+/// Uniform density over the whole sphere of directions.
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self) -> Vec3 {
+        Vec3::rand_unit()
+    }
+}
+
+/// Cosine weighted density over the hemisphere around `normal`, the ideal one for diffuse
+/// surfaces.
+pub struct CosinePdf {
+    normal: Vec3,
+}
+
+impl CosinePdf {
+    /// `normal` must be a unit vector.
+    pub fn new(normal: Vec3) -> Self {
+        CosinePdf { normal }
+    }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let cosine = Vec3::dot(&direction.normalized(), &self.normal);
+        cosine.max(0.0) / PI
+    }
+
+    fn generate(&self) -> Vec3 {
+        // the tip of the normal plus a point on the unit sphere lands with a cosine density
+        let direction = self.normal + Vec3::rand_unit();
+        if direction.near_zero() {
+            self.normal
+        } else {
+            direction
+        }
+    }
+}
+
+/// Density of the directions from `origin` toward the objects, to sample lights.
+pub struct HittablePdf<'a> {
+    objects: &'a dyn Hittable,
+    origin: Point,
+}
+
+impl<'a> HittablePdf<'a> {
+    pub fn new(objects: &'a dyn Hittable, origin: Point) -> Self {
+        HittablePdf { objects, origin }
+    }
+}
+
+impl Pdf for HittablePdf<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.objects.pdf_value(&self.origin, direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.objects.random(&self.origin)
+    }
+}
+
+/// Direction toward a sphere of `radius` whose center is `distance_squared` away along +z,
+/// uniform over the cone the sphere covers.
+pub fn random_to_sphere(radius: f64, distance_squared: f64) -> Vec3 {
+    let r1 = rand();
+    let r2 = rand();
+    let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).max(0.0).sqrt() - 1.0);
+
+    let phi = 2.0 * PI * r1;
+    let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+    Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z)
+}
+
+/// Solid angle density of a point sampled uniformly on a surface of `area`, seen along
+/// `direction` from `distance_squared` away, where the surface faces `normal`.
+pub fn area_to_solid_angle(area: f64, distance_squared: f64, direction: &Vec3, normal: &Vec3) -> f64 {
+    let cosine = Vec3::dot(&direction.normalized(), normal).abs();
+    if cosine < 1e-8 {
+        return 0.0;
+    }
+    distance_squared / (cosine * area)
+}
@@ -0,0 +1,112 @@
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+
+use super::vec3::Vec3;
+use super::Point;
+
+const POINT_COUNT: usize = 256;
+
+/// Gradient (Perlin) noise: a smooth random function of space, the base of the procedural
+/// textures.
+///
+/// The lattice is built from its own generator, so the same seed always gives the same
+/// noise, whatever the render seed or the thread sampling it.
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Pcg64Mcg::seed_from_u64(seed);
+
+        let gradients = (0..POINT_COUNT)
+            .map(|_| {
+                // uniform direction: rejection sample the unit ball, then project on the sphere
+                loop {
+                    let v = Vec3::new(
+                        rng.gen_range(-1.0..1.0),
+                        rng.gen_range(-1.0..1.0),
+                        rng.gen_range(-1.0..1.0),
+                    );
+                    let len_squared = v.len_squared();
+                    if 1e-12 < len_squared && len_squared <= 1.0 {
+                        break v.normalized();
+                    }
+                }
+            })
+            .collect();
+        let mut permutation = || {
+            let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+            perm.shuffle(&mut rng);
+            perm
+        };
+        let (perm_x, perm_y, perm_z) = (permutation(), permutation(), permutation());
+
+        Perlin {
+            gradients,
+            perm_x,
+            perm_y,
+            perm_z,
+        }
+    }
+
+    /// Noise value at `point`, roughly in -1..1 and 0 on every lattice point.
+    pub fn noise(&self, point: &Point) -> f64 {
+        let (x, y, z) = (*point.x(), *point.y(), *point.z());
+        let (u, v, w) = (x - x.floor(), y - y.floor(), z - z.floor());
+        let (i, j, k) = (x.floor() as i64, y.floor() as i64, z.floor() as i64);
+
+        // Hermite smoothing hides the lattice
+        let (uu, vv, ww) = (smooth(u), smooth(v), smooth(w));
+
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = &self.gradients[self.perm_x[wrap(i + di)]
+                        ^ self.perm_y[wrap(j + dj)]
+                        ^ self.perm_z[wrap(k + dk)]];
+                    let (fi, fj, fk) = (di as f64, dj as f64, dk as f64);
+                    let weight = Vec3::new(u - fi, v - fj, w - fk);
+
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * Vec3::dot(gradient, &weight);
+                }
+            }
+        }
+        accum
+    }
+
+    /// Sum of `depth` octaves of noise, each one at twice the frequency and half the weight
+    /// of the previous one. Always positive.
+    pub fn turbulence(&self, point: &Point, depth: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut point = *point;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(&point);
+            weight *= 0.5;
+            point = 2.0 * point;
+        }
+
+        accum.abs()
+    }
+}
+
+#[inline(always)]
+fn smooth(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[inline(always)]
+fn wrap(n: i64) -> usize {
+    (n & (POINT_COUNT as i64 - 1)) as usize
+}
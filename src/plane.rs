@@ -0,0 +1,137 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hittable::{HitResult, Hittable};
+use super::interval::Interval;
+use super::material::Material;
+use super::onb::Onb;
+use super::pdf::area_to_solid_angle;
+use super::ray::Ray;
+use super::utilities::rand;
+use super::vec3::Vec3;
+use super::Point;
+
+/// Infinite plane through `point`, facing `normal`.
+///
+/// The surface coordinates are distances from `point` along two axes of the plane, so a
+/// texture repeats every unit.
+pub struct Plane {
+    point: Point,
+    frame: Onb,
+    material: Arc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Vec3, material: Arc<dyn Material>) -> Self {
+        Plane {
+            point,
+            frame: Onb::new(&normal),
+            material,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitResult> {
+        let normal = self.frame.w();
+        let t = intersect_plane(&self.point, normal, ray, &interval)?;
+
+        let location = ray.at(t);
+        let planar = location - self.point;
+        let (u, v) = (Vec3::dot(&planar, self.frame.u()), Vec3::dot(&planar, self.frame.v()));
+
+        Some(HitResult::new(ray, location, *normal, Arc::clone(&self.material), t).with_uv(u, v))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::universe()
+    }
+}
+
+/// Disk of `radius` around `center`, facing `normal`.
+///
+/// `u` goes around the disk and `v` from the center (0) to the rim (1).
+pub struct Disk {
+    center: Point,
+    radius: f64,
+    frame: Onb,
+    material: Arc<dyn Material>,
+    bbox: Aabb,
+}
+
+impl Disk {
+    pub fn new(center: Point, normal: Vec3, radius: f64, material: Arc<dyn Material>) -> Self {
+        let frame = Onb::new(&normal);
+        // along every axis the rim reaches radius * sin(angle between the axis and the normal)
+        let n = frame.w();
+        let extent = Vec3::new(
+            radius * (1.0 - n.x() * n.x()).max(0.0).sqrt(),
+            radius * (1.0 - n.y() * n.y()).max(0.0).sqrt(),
+            radius * (1.0 - n.z() * n.z()).max(0.0).sqrt(),
+        );
+
+        Disk {
+            center,
+            radius,
+            frame,
+            material,
+            bbox: Aabb::from_points(center - extent, center + extent),
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitResult> {
+        let normal = self.frame.w();
+        let t = intersect_plane(&self.center, normal, ray, &interval)?;
+
+        let location = ray.at(t);
+        let planar = location - self.center;
+        let distance_squared = planar.len_squared();
+        if distance_squared > self.radius * self.radius {
+            return None;
+        }
+
+        let angle = f64::atan2(Vec3::dot(&planar, self.frame.v()), Vec3::dot(&planar, self.frame.u()));
+        let u = angle / (2.0 * PI) + 0.5;
+        let v = distance_squared.sqrt() / self.radius;
+
+        Some(HitResult::new(ray, location, *normal, Arc::clone(&self.material), t).with_uv(u, v))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        match self.hit(&Ray::new(*origin, *direction), Interval::new(0.001, f64::INFINITY)) {
+            Some(hit) => {
+                let distance_squared = hit.t() * hit.t() * direction.len_squared();
+                let area = PI * self.radius * self.radius;
+                area_to_solid_angle(area, distance_squared, direction, self.frame.w())
+            }
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point) -> Vec3 {
+        // the square root keeps the points uniform over the area rather than the radius
+        let r = self.radius * rand().sqrt();
+        let (sin, cos) = (2.0 * PI * rand()).sin_cos();
+        self.center + self.frame.transform(&Vec3::new(r * cos, r * sin, 0.0)) - *origin
+    }
+}
+
+/// Ray parameter where `ray` crosses the plane through `point` with unit `normal`, if it does
+/// within `interval`.
+fn intersect_plane(point: &Point, normal: &Vec3, ray: &Ray, interval: &Interval) -> Option<f64> {
+    let denominator = Vec3::dot(normal, &ray.direction());
+    // parallel to the plane
+    if denominator.abs() < 1e-8 {
+        return None;
+    }
+
+    let t = Vec3::dot(normal, &(*point - ray.origin())) / denominator;
+    interval.contains(t).then_some(t)
+}
@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hittable::{HitResult, Hittable};
+use super::hittable_list::HittableList;
+use super::interval::Interval;
+use super::material::Material;
+use super::pdf::area_to_solid_angle;
+use super::ray::Ray;
+use super::utilities::rand;
+use super::vec3::Vec3;
+use super::Point;
+
+/// Parallelogram with corner `q` and sides `u` and `v`.
+///
+/// The normal is `u x v`, so with the sides counterclockwise the quad faces the viewer. The
+/// surface coordinates go from 0 to 1 along `u` and `v`.
+pub struct Quad {
+    q: Point,
+    u: Vec3,
+    v: Vec3,
+    /// `n / (n . n)` with `n = u x v`, turns the planar hit offset into (u, v) coordinates
+    w: Vec3,
+    normal: Vec3,
+    /// Plane equation constant, `normal . p = d` for every point of the plane
+    d: f64,
+    area: f64,
+    material: Arc<dyn Material>,
+    bbox: Aabb,
+}
+
+impl Quad {
+    pub fn new(q: Point, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
+        let n = Vec3::cross(&u, &v);
+        let normal = n.normalized();
+        let bbox = Aabb::enclosing(
+            &Aabb::from_points(q, q + u + v),
+            &Aabb::from_points(q + u, q + v),
+        );
+
+        Quad {
+            q,
+            u,
+            v,
+            w: n / Vec3::dot(&n, &n),
+            normal,
+            d: Vec3::dot(&normal, &q),
+            area: n.len(),
+            material,
+            bbox,
+        }
+    }
+
+    /// The corner, sides and normal of the quad, e.g. to sample points on a light.
+    #[inline(always)]
+    pub fn q(&self) -> Point {
+        self.q
+    }
+
+    #[inline(always)]
+    pub fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    #[inline(always)]
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitResult> {
+        let denominator = Vec3::dot(&self.normal, &ray.direction());
+        // parallel to the plane
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - Vec3::dot(&self.normal, &ray.origin())) / denominator;
+        if !interval.contains(t) {
+            return None;
+        }
+
+        let location = ray.at(t);
+        let planar = location - self.q;
+        let alpha = Vec3::dot(&self.w, &Vec3::cross(&planar, &self.v));
+        let beta = Vec3::dot(&self.w, &Vec3::cross(&self.u, &planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some(
+            HitResult::new(ray, location, self.normal, Arc::clone(&self.material), t)
+                .with_uv(alpha, beta),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        match self.hit(&Ray::new(*origin, *direction), Interval::new(0.001, f64::INFINITY)) {
+            Some(hit) => {
+                let distance_squared = hit.t() * hit.t() * direction.len_squared();
+                area_to_solid_angle(self.area, distance_squared, direction, &self.normal)
+            }
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point) -> Vec3 {
+        self.q + rand() * self.u + rand() * self.v - *origin
+    }
+}
+
+/// Axis-aligned box with opposite corners `a` and `b`, as six outward facing quads.
+pub fn make_box(a: Point, b: Point, material: Arc<dyn Material>) -> HittableList {
+    let min = Point::new(a.x().min(*b.x()), a.y().min(*b.y()), a.z().min(*b.z()));
+    let max = Point::new(a.x().max(*b.x()), a.y().max(*b.y()), a.z().max(*b.z()));
+
+    let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+    let mut sides = HittableList::new();
+    let mut side = |q: Point, u: Vec3, v: Vec3| sides.add(Arc::new(Quad::new(q, u, v, Arc::clone(&material))));
+    side(Point::new(*min.x(), *min.y(), *max.z()), dx, dy); // front
+    side(Point::new(*max.x(), *min.y(), *max.z()), -dz, dy); // right
+    side(Point::new(*max.x(), *min.y(), *min.z()), -dx, dy); // back
+    side(Point::new(*min.x(), *min.y(), *min.z()), dz, dy); // left
+    side(Point::new(*min.x(), *max.y(), *max.z()), dx, -dz); // top
+    side(Point::new(*min.x(), *min.y(), *min.z()), dx, dz); // bottom
+    sides
+}
@@ -1,14 +1,11 @@
-use std::f64::INFINITY;
-
-use super::color::Color;
-use super::hittable::Hittable;
-use super::interval::Interval;
 use super::vec3::Vec3;
 use super::Point;
 
+#[derive(Debug, Clone, Copy)]
 pub struct Ray {
     origin: Point,
     direction: Vec3,
+    time: f64,
 }
 
 impl Ray {
@@ -16,11 +13,22 @@ impl Ray {
         Ray {
             origin: Point::zero(),
             direction: Vec3::zero(),
+            time: 0.0,
         }
     }
 
     pub fn new(origin: Point, direction: Vec3) -> Self {
-        Ray { origin, direction }
+        Ray::with_time(origin, direction, 0.0)
+    }
+
+    /// Ray leaving at `time` within the camera shutter interval, moving objects are hit where
+    /// they are at that time.
+    pub fn with_time(origin: Point, direction: Vec3, time: f64) -> Self {
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
 
     #[inline(always)]
@@ -33,34 +41,12 @@ impl Ray {
         self.direction
     }
 
-    pub fn at(&self, t: f64) -> Point {
-        self.origin + t * self.direction
+    #[inline(always)]
+    pub fn time(&self) -> f64 {
+        self.time
     }
 
-    pub fn color(&self, world: &dyn Hittable, depth: u32) -> Color {
-        if depth <= 0 {
-            return Color::black();
-        }
-        // interval starts from 0.001 to hack away the shadow acne problem
-        let hit = world.hit(&self, Interval::new(0.001, INFINITY));
-        match hit {
-            None => (),
-            Some(result) => {
-                // we need to check the scatter result now
-                let scatter_result = result.material().scatter(&self, &result);
-                match scatter_result {
-                    None => return Color::black(),
-                    Some(scatter) => {
-                        return scatter.attenuation()
-                            * scatter.scattered_ray().color(world, depth - 1)
-                    }
-                }
-            }
-        }
-        // background
-        // first map a from -1..1 to 0..1
-        let a = 0.5 * (self.direction.normalized().y() + 1.0);
-        // now lerp the colors
-        (1.0 - a) * Color::white() + a * Color::new(0.5, 0.7, 1.0)
+    pub fn at(&self, t: f64) -> Point {
+        self.origin + t * self.direction
     }
 }
@@ -1,18 +1,34 @@
-use crate::{hittable_list::HittableList, Point};
+use crate::{hittable::Hittable, Point};
 
+use super::aov::{Aov, Aovs};
 use super::camera::Camera;
+use super::checkpoint::{self, Checkpoint};
+use super::denoise::Denoiser;
+use super::framebuffer::Framebuffer;
+use super::tonemap::ToneMapping;
 use crate::utilities::print_duration;
 
+use rayon::ThreadPool;
+
+use std::io::{self, ErrorKind};
+use std::sync::Arc;
 use std::{fmt::Display, time::SystemTime};
 
 #[derive(Debug)]
 pub struct Raytracer {
-    data: Vec<u8>,
+    framebuffer: Framebuffer,
     camera: Camera,
     components: i32,
+    tone_mapping: ToneMapping,
+    aov_list: Vec<Aov>,
+    aovs: Option<Aovs>,
+    denoiser: Option<Denoiser>,
+    /// Threads of the last render, reused by the denoiser.
+    pool: Option<Arc<ThreadPool>>,
 }
 
 impl Raytracer {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         aspect_ratio: f64,
         image_width: usize,
@@ -35,49 +51,209 @@ impl Raytracer {
             focus_dist,
             defocus_angle,
         );
-        let components = 3;
-        let data = vec![0; camera.width() * camera.height() * components];
+        Raytracer::with_camera(camera)
+    }
+
+    pub fn with_camera(camera: Camera) -> Self {
+        let framebuffer = Framebuffer::new(camera.width(), camera.height());
 
         Raytracer {
-            data,
+            framebuffer,
             camera,
             //camera: Camera::default(),
-            components: components as i32,
+            components: Framebuffer::COMPONENTS as i32,
+            tone_mapping: ToneMapping::default(),
+            aov_list: Vec::new(),
+            aovs: None,
+            denoiser: None,
+            pool: None,
         }
     }
 
-    pub fn render(&mut self, world: &HittableList, options: RaytracerOptions) {
-        match options.execution_method() {
-            ExecutionMethod::Single => self.render_image(world),
-            ExecutionMethod::Parallel => self.render_image_parallel(world),
-        }
+    pub fn render(&mut self, world: &dyn Hittable, options: RaytracerOptions) {
+        self.render_progressive(world, options, |_| {});
     }
 
-    fn render_image(&mut self, world: &HittableList) {
+    /// Renders in passes of `RaytracerOptions::pass_samples` samples per pixel until every
+    /// pixel has the camera's samples, calling `after_pass` whenever a pass is done so the
+    /// intermediate image or a checkpoint can be saved.
+    ///
+    /// Samples already in the framebuffer, e.g. from `resume`, are kept and not rendered again.
+    /// With adaptive sampling the passes go on until every pixel has converged.
+    pub fn render_progressive<F: FnMut(&Raytracer)>(
+        &mut self,
+        world: &dyn Hittable,
+        options: RaytracerOptions,
+        mut after_pass: F,
+    ) {
+        // the AOVs and the denoiser run in the pool too, on a single thread for
+        // `ExecutionMethod::Single`
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(match options.execution_method() {
+                ExecutionMethod::Single => 1,
+                ExecutionMethod::Parallel => options.threads(),
+            })
+            .build()
+            .unwrap();
+        let pool = Arc::clone(self.pool.insert(Arc::new(pool)));
+        // adaptive sampling checks the pixels after every pass, by default as often as it can
+        let pass_samples = options
+            .pass_samples()
+            .unwrap_or(match self.camera.adaptive_sampling() {
+                Some(adaptive) => adaptive.min_samples(),
+                None => self.camera.samples(),
+            })
+            .max(1);
+
         let start = SystemTime::now();
-        self.camera.render(world, &mut self.data);
+        let aov_list = self.rendered_aovs();
+        if !aov_list.is_empty() && self.aovs.is_none() {
+            let camera = &self.camera;
+            self.aovs = Some(pool.install(|| camera.render_aovs(world, &aov_list)));
+        }
+        loop {
+            let (camera, image_data) = (&self.camera, self.framebuffer.data_mut());
+            let remaining = match options.execution_method() {
+                ExecutionMethod::Single => camera.render(world, image_data, pass_samples),
+                ExecutionMethod::Parallel => pool.install(|| camera.render_parallel(world, image_data, pass_samples)),
+            };
+            println!(
+                "Samples: {}/{}, pixels remaining: {}",
+                self.framebuffer.min_samples(),
+                self.camera.samples(),
+                remaining
+            );
+            after_pass(self);
+            if remaining == 0 {
+                break;
+            }
+        }
         let end = start.elapsed().unwrap();
         print!("Rendering duration: ");
         print_duration(end);
     }
 
-    fn render_image_parallel(&mut self, world: &HittableList) {
-        let start = SystemTime::now();
-        self.camera.render_parallel(world, &mut self.data);
-        let end = start.elapsed().unwrap();
-        print!("Rendering duration: ");
-        print_duration(end);
+    /// Continues the render saved in `checkpoint`, which must come from a camera with the same
+    /// image size and seed.
+    pub fn resume(&mut self, checkpoint: Checkpoint) -> io::Result<()> {
+        let framebuffer = checkpoint.framebuffer;
+        if (framebuffer.width(), framebuffer.height()) != (self.camera.width(), self.camera.height()) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "the checkpoint is {}x{} but the image is {}x{}",
+                    framebuffer.width(),
+                    framebuffer.height(),
+                    self.camera.width(),
+                    self.camera.height()
+                ),
+            ));
+        }
+        if checkpoint.seed != self.camera.seed() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "the checkpoint was rendered with seed {}, not {}",
+                    checkpoint.seed,
+                    self.camera.seed()
+                ),
+            ));
+        }
+
+        self.framebuffer = framebuffer;
+        Ok(())
     }
 
-    pub fn save_image(&self, filename: &str) {
-        image::save_buffer(
-            filename,
-            &self.data,
-            self.camera.width() as u32,
-            self.camera.height() as u32,
-            image::ColorType::Rgb8,
-        )
-        .unwrap();
+    /// Saves the samples accumulated so far, see `checkpoint::save`.
+    pub fn save_checkpoint(&self, path: &str) -> io::Result<()> {
+        checkpoint::save(path, self.camera.seed(), &self.framebuffer)
+    }
+
+    /// Tone mapping used when saving to 8-bit formats.
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.tone_mapping = tone_mapping;
+    }
+
+    /// AOVs to render along the image and save with it. They are rendered once, before the
+    /// first pass.
+    pub fn set_aovs(&mut self, aovs: Vec<Aov>) {
+        self.aov_list = aovs;
+        self.aovs = None;
+    }
+
+    /// Denoiser applied to the image when it is saved, see `save_image`. Its guide AOVs are
+    /// rendered along the image, but only saved if asked for with `set_aovs`.
+    pub fn set_denoiser(&mut self, denoiser: Option<Denoiser>) {
+        self.denoiser = denoiser;
+        self.aovs = None;
+    }
+
+    /// Saves the rendered image, see `Framebuffer::save` for the supported formats. With a
+    /// denoiser the saved image is the denoised one, the samples themselves are kept as they
+    /// are so rendering can go on.
+    ///
+    /// Rendered AOVs are saved too: as extra channels of the same file for OpenEXR, as files
+    /// of their own named after the AOV for other formats (see `Aovs::save_separate`).
+    pub fn save_image(&self, filename: &str) -> image::ImageResult<()> {
+        let denoised = self.denoised();
+        let framebuffer = denoised.as_ref().unwrap_or(&self.framebuffer);
+        let saved_aovs = self.aovs.as_ref().filter(|_| !self.aov_list.is_empty());
+        let Some(aovs) = saved_aovs.map(|aovs| aovs.select(&self.aov_list)) else {
+            return framebuffer.save(filename, &self.tone_mapping);
+        };
+
+        match image::ImageFormat::from_path(filename)? {
+            image::ImageFormat::OpenExr => aovs.save_exr(filename, &framebuffer.radiance()),
+            _ => {
+                framebuffer.save(filename, &self.tone_mapping)?;
+                aovs.save_separate(filename)
+            }
+        }
+    }
+
+    /// Saves the image as rendered, without denoising nor AOVs.
+    pub fn save_raw_image(&self, filename: &str) -> image::ImageResult<()> {
+        self.framebuffer.save(filename, &self.tone_mapping)
+    }
+
+    /// Saves a false color image of how many samples every pixel received, useful to tune
+    /// adaptive sampling.
+    pub fn save_heatmap(&self, filename: &str) -> image::ImageResult<()> {
+        self.framebuffer.sample_heatmap(self.camera.samples()).save(filename)
+    }
+
+    #[inline(always)]
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    /// AOVs rendered so far, `None` before the first pass or if none were needed.
+    #[inline(always)]
+    pub fn aovs(&self) -> Option<&Aovs> {
+        self.aovs.as_ref()
+    }
+
+    /// Denoised copy of the framebuffer, `None` without a denoiser or before the first pass.
+    /// Runs on the threads the image was rendered with.
+    pub fn denoised(&self) -> Option<Framebuffer> {
+        let (denoiser, aovs) = (self.denoiser?, self.aovs.as_ref()?);
+        match &self.pool {
+            Some(pool) => pool.install(|| denoiser.denoise(&self.framebuffer, aovs)),
+            None => denoiser.denoise(&self.framebuffer, aovs),
+        }
+    }
+
+    /// AOVs asked for, followed by the ones the denoiser needs.
+    fn rendered_aovs(&self) -> Vec<Aov> {
+        let mut aovs = self.aov_list.clone();
+        if self.denoiser.is_some() {
+            for guide in Denoiser::GUIDES {
+                if !aovs.contains(&guide) {
+                    aovs.push(guide);
+                }
+            }
+        }
+        aovs
     }
 }
 
@@ -101,14 +277,41 @@ pub enum ExecutionMethod {
 
 pub struct RaytracerOptions {
     execution_method: ExecutionMethod,
+    threads: usize,
+    pass_samples: Option<u32>,
 }
 
 impl RaytracerOptions {
     pub fn new(execution_method: ExecutionMethod) -> Self {
-        RaytracerOptions { execution_method }
+        RaytracerOptions {
+            execution_method,
+            threads: 0,
+            pass_samples: None,
+        }
+    }
+
+    /// Number of worker threads for `ExecutionMethod::Parallel`, 0 uses one per core.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Samples per pixel of every progressive pass, by default all of them in a single pass.
+    /// Passes take at least one sample, a pass of none would never finish the render.
+    pub fn with_pass_samples(mut self, pass_samples: u32) -> Self {
+        self.pass_samples = Some(pass_samples.max(1));
+        self
     }
 
     pub fn execution_method(&self) -> ExecutionMethod {
         self.execution_method
     }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn pass_samples(&self) -> Option<u32> {
+        self.pass_samples
+    }
 }
@@ -0,0 +1,52 @@
+use super::color::Color;
+use super::framebuffer::Framebuffer;
+
+/// Below this mean luminance the error is measured in absolute terms, otherwise almost black
+/// pixels would never look converged.
+const MIN_LUMINANCE: f64 = 0.01;
+
+/// Stops sampling a pixel once its estimate is good enough, spending the remaining samples on
+/// the noisy parts of the image.
+///
+/// Every pixel gets at least `min_samples`, then keeps receiving samples until the relative
+/// standard error of its mean luminance drops below `threshold` or it reaches the camera's
+/// sample count.
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveSampling {
+    min_samples: u32,
+    threshold: f64,
+}
+
+impl AdaptiveSampling {
+    pub fn new(min_samples: u32, threshold: f64) -> Self {
+        AdaptiveSampling {
+            min_samples: min_samples.max(2),
+            threshold,
+        }
+    }
+
+    #[inline(always)]
+    pub fn min_samples(&self) -> u32 {
+        self.min_samples
+    }
+
+    #[inline(always)]
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Whether the accumulated `pixel` (see `Framebuffer`) needs no more samples.
+    pub fn converged(&self, pixel: &[f64]) -> bool {
+        let samples = pixel[Framebuffer::SAMPLES];
+        if samples < self.min_samples as f64 {
+            return false;
+        }
+
+        let sum = Color::new(pixel[0], pixel[1], pixel[2]).luminance();
+        let mean = sum / samples;
+        let variance = ((pixel[Framebuffer::SQUARES] - sum * mean) / (samples - 1.0)).max(0.0);
+        let standard_error = (variance / samples).sqrt();
+
+        standard_error / mean.max(MIN_LUMINANCE) < self.threshold
+    }
+}
@@ -0,0 +1,792 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use super::bvh::BvhNode;
+use super::camera::Camera;
+use super::color::Color;
+use super::environment::{ConstantEnvironment, Environment, EnvironmentMap, GradientEnvironment};
+use super::hittable::Hittable;
+use super::hittable_list::HittableList;
+use super::material::{
+    Dielectric, DiffuseLight, Isotropic, Lambertian, Material, MaterialIds, Metal, MetallicRoughness,
+};
+use super::matrix::Matrix4;
+use super::medium::ConstantMedium;
+use super::obj::load_obj;
+use super::plane::{Disk, Plane};
+use super::quad::{make_box, Quad};
+use super::sphere::Sphere;
+use super::texture::{
+    CheckerTexture, ImageTexture, MarbleTexture, NoiseTexture, SolidColor, Texture, WoodTexture,
+};
+use super::transform::Transform;
+use super::triangle::Triangle;
+use super::vec3::Vec3;
+use super::Point;
+
+/// A camera and a world loaded from a TOML scene description.
+///
+/// ```toml
+/// [camera]
+/// image_width = 400
+/// lookfrom = [13.0, 2.0, 3.0]
+/// lookat = [0.0, 0.0, 0.0]
+///
+/// [materials.ground]
+/// type = "lambertian"
+/// albedo = [0.5, 0.5, 0.5]
+///
+/// [[objects]]
+/// type = "sphere"
+/// center = [0.0, -1000.0, 0.0]
+/// radius = 1000.0
+/// material = "ground"
+/// ```
+pub struct Scene {
+    pub camera: Camera,
+    pub world: HittableList,
+}
+
+impl Scene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+        let source = fs::read_to_string(&path).map_err(|err| SceneError {
+            line: None,
+            message: err.to_string(),
+        })?;
+        let base_dir = path.as_ref().parent().unwrap_or(Path::new(""));
+        Scene::parse_relative_to(&source, base_dir)
+    }
+
+    /// Parses a scene, resolving the files it references against the working directory.
+    pub fn parse(source: &str) -> Result<Scene, SceneError> {
+        Scene::parse_relative_to(source, Path::new(""))
+    }
+
+    fn parse_relative_to(source: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+        let desc: SceneDesc = toml::from_str(source).map_err(|err| SceneError {
+            line: err.span().map(|span| line_of(source, span.start)),
+            message: err.message().to_string(),
+        })?;
+
+        let mut materials: HashMap<&str, Arc<dyn Material>> = HashMap::new();
+        let mut light_materials: HashSet<&str> = HashSet::new();
+        // numbered in the order of their names, TOML tables don't keep the order of the file
+        let mut material_ids = MaterialIds::new();
+        let mut material_descs: Vec<_> = desc.materials.iter().collect();
+        material_descs.sort_by_key(|(name, _)| name.as_str());
+        for (name, material) in material_descs {
+            let built = material.get_ref().build(base_dir).map_err(|message| SceneError {
+                line: Some(line_of(source, material.span().start)),
+                message,
+            })?;
+            materials.insert(name, material_ids.number(built));
+            if let MaterialDesc::DiffuseLight { .. } = material.get_ref() {
+                light_materials.insert(name);
+            }
+        }
+
+        let mut world = HittableList::new();
+        // emissive objects that can be sampled are also added to the lights
+        let mut lights = HittableList::new();
+        // every OBJ file is loaded once and instanced by all the objects using it
+        let mut obj_files: HashMap<(String, Option<String>), Arc<dyn Hittable>> = HashMap::new();
+        for object in desc.objects {
+            // objects are deserialized one by one so errors point at the offending entry
+            // rather than at the start of the `[[objects]]` array
+            let line = line_of(source, object.span().start);
+            let error = |message: String| SceneError {
+                line: Some(line),
+                message,
+            };
+            let mut object = object.into_inner();
+            let transform = match object.as_table_mut().and_then(|table| table.remove("transform")) {
+                Some(transform) => {
+                    let transform: TransformDesc = transform
+                        .try_into()
+                        .map_err(|err: toml::de::Error| error(format!("in `transform`: {}", err.message())))?;
+                    Some(transform.matrix().map_err(error)?)
+                }
+                None => None,
+            };
+            let density = match object.as_table_mut().and_then(|table| table.remove("density")) {
+                Some(density) => match density.as_float().or(density.as_integer().map(|d| d as f64)) {
+                    Some(density) if density > 0.0 => Some(density),
+                    _ => return Err(error("`density` must be a positive number".to_string())),
+                },
+                None => None,
+            };
+            let object: ObjectDesc = object
+                .try_into()
+                .map_err(|err: toml::de::Error| error(err.message().to_string()))?;
+            let material = |name: &str| {
+                materials
+                    .get(name)
+                    .map(Arc::clone)
+                    .ok_or_else(|| error(format!("unknown material `{}`", name)))
+            };
+
+            let object_is_light = !matches!(object, ObjectDesc::Plane { .. } | ObjectDesc::Obj { .. })
+                && object.material().is_some_and(|name| light_materials.contains(name));
+            let phase_function = match object.material() {
+                Some(name) if density.is_some() => Some(material(name)?),
+                _ => None,
+            };
+
+            let hittable: Arc<dyn Hittable> = match object {
+                ObjectDesc::Sphere {
+                    center,
+                    end_center,
+                    radius,
+                    material: name,
+                } => Arc::new(Sphere::moving(
+                    to_point(&center),
+                    to_point(&end_center.unwrap_or(center)),
+                    radius,
+                    material(&name)?,
+                )),
+                ObjectDesc::Triangle {
+                    vertices: [a, b, c],
+                    normals,
+                    material: name,
+                } => {
+                    let (a, b, c) = (to_point(&a), to_point(&b), to_point(&c));
+                    Arc::new(match normals {
+                        Some([na, nb, nc]) => Triangle::with_normals(
+                            a,
+                            b,
+                            c,
+                            [to_point(&na), to_point(&nb), to_point(&nc)],
+                            material(&name)?,
+                        ),
+                        None => Triangle::new(a, b, c, material(&name)?),
+                    })
+                }
+                ObjectDesc::Quad {
+                    corner,
+                    u,
+                    v,
+                    material: name,
+                } => {
+                    let (u, v) = (to_point(&u), to_point(&v));
+                    if Vec3::cross(&u, &v).near_zero() {
+                        return Err(error(String::from("the sides `u` and `v` can't be parallel")));
+                    }
+                    Arc::new(Quad::new(to_point(&corner), u, v, material(&name)?))
+                }
+                ObjectDesc::Disk {
+                    center,
+                    normal,
+                    radius,
+                    material: name,
+                } => Arc::new(Disk::new(to_point(&center), to_normal(&normal).map_err(error)?, radius, material(&name)?)),
+                ObjectDesc::Plane {
+                    point,
+                    normal,
+                    material: name,
+                } => Arc::new(Plane::new(to_point(&point), to_normal(&normal).map_err(error)?, material(&name)?)),
+                ObjectDesc::Cuboid {
+                    min,
+                    max,
+                    material: name,
+                } => Arc::new(make_box(to_point(&min), to_point(&max), material(&name)?)),
+                ObjectDesc::Obj {
+                    path,
+                    material: name,
+                } => match obj_files.get(&(path.clone(), name.clone())) {
+                    Some(meshes) => Arc::clone(meshes),
+                    None => {
+                        let default_material = match &name {
+                            Some(name) => material(name)?,
+                            None => material_ids.number(Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)))),
+                        };
+                        let meshes = load_obj(base_dir.join(&path), default_material, &mut material_ids)
+                            .map_err(|err| error(err.to_string()))?;
+                        let meshes: Arc<dyn Hittable> = Arc::new(BvhNode::from(meshes));
+                        obj_files.insert((path, name), Arc::clone(&meshes));
+                        meshes
+                    }
+                },
+            };
+
+            let hittable: Arc<dyn Hittable> = match transform {
+                Some(matrix) => Arc::new(Transform::new(hittable, matrix)),
+                None => hittable,
+            };
+            // the medium wraps the placed object, so the density is per world unit
+            match (density, phase_function) {
+                (Some(density), Some(phase_function)) => {
+                    world.add(Arc::new(ConstantMedium::new(hittable, density, phase_function)))
+                }
+                (Some(_), None) => return Err(error("a medium needs a `material`".to_string())),
+                (None, _) => {
+                    if object_is_light {
+                        lights.add(Arc::clone(&hittable));
+                    }
+                    world.add(hittable)
+                }
+            }
+        }
+
+        let mut camera = desc.camera.build(source)?;
+        if let Some(background) = desc.background {
+            let line = line_of(source, background.span().start);
+            let background = background.get_ref().build(base_dir).map_err(|message| SceneError {
+                line: Some(line),
+                message,
+            })?;
+            camera.set_background(background);
+        }
+        camera.set_lights(Arc::new(lights));
+
+        Ok(Scene { camera, world })
+    }
+}
+
+#[derive(Debug)]
+pub struct SceneError {
+    line: Option<usize>,
+    message: String,
+}
+
+impl SceneError {
+    /// 1-based line of the scene file the error refers to, if known.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+/// Value of an optional field, `default` if missing, or an error pointing at its line if
+/// `valid` rejects it.
+fn checked<T: Copy>(
+    source: &str,
+    value: &Option<Spanned<T>>,
+    default: T,
+    valid: fn(&T) -> bool,
+    message: &str,
+) -> Result<T, SceneError> {
+    match value {
+        Some(value) if !valid(value.get_ref()) => Err(SceneError {
+            line: Some(line_of(source, value.span().start)),
+            message: message.to_string(),
+        }),
+        Some(value) => Ok(*value.get_ref()),
+        None => Ok(default),
+    }
+}
+
+fn to_point(v: &[f64; 3]) -> Point {
+    Point::new(v[0], v[1], v[2])
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    camera: CameraDesc,
+    background: Option<Spanned<BackgroundDesc>>,
+    #[serde(default)]
+    materials: HashMap<String, Spanned<MaterialDesc>>,
+    #[serde(default)]
+    objects: Vec<Spanned<toml::Value>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    aspect_ratio: Option<Spanned<f64>>,
+    image_width: Option<Spanned<usize>>,
+    samples: Option<Spanned<u32>>,
+    #[serde(default = "CameraDesc::default_max_depth")]
+    max_depth: u32,
+    #[serde(default = "CameraDesc::default_vfov")]
+    vfov: f64,
+    lookfrom: [f64; 3],
+    lookat: [f64; 3],
+    #[serde(default = "CameraDesc::default_focus_dist")]
+    focus_dist: f64,
+    #[serde(default)]
+    defocus_angle: f64,
+    /// Shutter open and close times, moving objects are blurred over the interval.
+    #[serde(default)]
+    shutter: [f64; 2],
+}
+
+impl CameraDesc {
+    fn default_aspect_ratio() -> f64 {
+        16.0 / 9.0
+    }
+
+    fn default_image_width() -> usize {
+        400
+    }
+
+    fn default_samples() -> u32 {
+        100
+    }
+
+    fn default_max_depth() -> u32 {
+        50
+    }
+
+    fn default_vfov() -> f64 {
+        90.0
+    }
+
+    fn default_focus_dist() -> f64 {
+        10.0
+    }
+
+    /// Camera described, or an error pointing at the line of the first value out of range.
+    fn build(&self, source: &str) -> Result<Camera, SceneError> {
+        let aspect_ratio = checked(
+            source,
+            &self.aspect_ratio,
+            CameraDesc::default_aspect_ratio(),
+            |aspect| aspect.is_finite() && *aspect > 0.0,
+            "`aspect_ratio` must be a positive number",
+        )?;
+        let image_width = checked(
+            source,
+            &self.image_width,
+            CameraDesc::default_image_width(),
+            |width| *width > 0,
+            "`image_width` must be at least 1",
+        )?;
+        let samples = checked(
+            source,
+            &self.samples,
+            CameraDesc::default_samples(),
+            |samples| *samples > 0,
+            "`samples` must be at least 1",
+        )?;
+
+        let mut camera = Camera::new(
+            aspect_ratio,
+            image_width,
+            samples,
+            self.max_depth,
+            self.vfov,
+            to_point(&self.lookfrom),
+            to_point(&self.lookat),
+            self.focus_dist,
+            self.defocus_angle,
+        );
+        camera.set_shutter(self.shutter[0], self.shutter[1]);
+        Ok(camera)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian { albedo: ColorOrTexture },
+    Metal { albedo: ColorOrTexture, fuzz: f64 },
+    Dielectric { refraction_index: f64 },
+    DiffuseLight { emit: [f64; 3] },
+    Isotropic { albedo: ColorOrTexture },
+    /// glTF style physically based material, with the glTF defaults
+    MetallicRoughness {
+        base_color: ColorOrTexture,
+        #[serde(default = "MaterialDesc::default_factor")]
+        metallic: f64,
+        #[serde(default = "MaterialDesc::default_factor")]
+        roughness: f64,
+        /// complex index of refraction of the metal, replacing the base color
+        eta: Option<[f64; 3]>,
+        k: Option<[f64; 3]>,
+    },
+}
+
+impl MaterialDesc {
+    /// Metallic and roughness factors of glTF materials that don't give them.
+    fn default_factor() -> f64 {
+        1.0
+    }
+
+    fn build(&self, base_dir: &Path) -> Result<Arc<dyn Material>, String> {
+        Ok(match self {
+            MaterialDesc::Lambertian { albedo } => Arc::new(Lambertian::textured(albedo.0.build(base_dir)?)),
+            MaterialDesc::Metal { albedo, fuzz } => Arc::new(Metal::textured(albedo.0.build(base_dir)?, *fuzz)),
+            MaterialDesc::Dielectric { refraction_index } => {
+                Arc::new(Dielectric::new(*refraction_index))
+            }
+            MaterialDesc::DiffuseLight { emit } => Arc::new(DiffuseLight::new(to_color(emit))),
+            MaterialDesc::Isotropic { albedo } => Arc::new(Isotropic::textured(albedo.0.build(base_dir)?)),
+            MaterialDesc::MetallicRoughness {
+                base_color,
+                metallic,
+                roughness,
+                eta,
+                k,
+            } => {
+                if !(0.0..=1.0).contains(metallic) || !(0.0..=1.0).contains(roughness) {
+                    return Err(String::from("`metallic` and `roughness` must be between 0 and 1"));
+                }
+                let material = MetallicRoughness::textured(base_color.0.build(base_dir)?, *metallic, *roughness);
+                match (eta, k) {
+                    (Some(eta), Some(k)) => Arc::new(material.with_conductor(to_color(eta), to_color(k))),
+                    (None, None) => Arc::new(material),
+                    _ => return Err(String::from("`eta` and `k` must be given together")),
+                }
+            }
+        })
+    }
+}
+
+/// Either a plain `[r, g, b]` color or a texture table.
+#[derive(Deserialize)]
+#[serde(try_from = "toml::Value")]
+struct ColorOrTexture(TextureDesc);
+
+impl TryFrom<toml::Value> for ColorOrTexture {
+    type Error = toml::de::Error;
+
+    fn try_from(value: toml::Value) -> Result<Self, Self::Error> {
+        match value {
+            toml::Value::Array(_) => Ok(ColorOrTexture(TextureDesc::Solid {
+                color: value.try_into()?,
+            })),
+            _ => Ok(ColorOrTexture(value.try_into()?)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid {
+        color: [f64; 3],
+    },
+    /// 3D checkerboard of `scale` sized cubes
+    Checker {
+        scale: f64,
+        even: Box<ColorOrTexture>,
+        odd: Box<ColorOrTexture>,
+    },
+    /// Image mapped over the surface coordinates
+    Image {
+        path: String,
+    },
+    /// Gray Perlin noise
+    Noise {
+        scale: f64,
+        #[serde(default)]
+        seed: u64,
+    },
+    Marble {
+        scale: f64,
+        #[serde(default = "TextureDesc::default_marble_turbulence")]
+        turbulence: f64,
+        #[serde(default)]
+        seed: u64,
+        #[serde(default)]
+        dark: Option<[f64; 3]>,
+        #[serde(default)]
+        light: Option<[f64; 3]>,
+    },
+    /// Growth rings around the y axis
+    Wood {
+        scale: f64,
+        #[serde(default = "TextureDesc::default_wood_turbulence")]
+        turbulence: f64,
+        #[serde(default)]
+        seed: u64,
+        #[serde(default)]
+        dark: Option<[f64; 3]>,
+        #[serde(default)]
+        light: Option<[f64; 3]>,
+    },
+}
+
+impl TextureDesc {
+    fn build(&self, base_dir: &Path) -> Result<Arc<dyn Texture>, String> {
+        Ok(match self {
+            TextureDesc::Solid { color } => Arc::new(SolidColor::new(to_color(color))),
+            TextureDesc::Checker { scale, even, odd } => Arc::new(CheckerTexture::new(
+                *scale,
+                even.0.build(base_dir)?,
+                odd.0.build(base_dir)?,
+            )),
+            TextureDesc::Image { path } => Arc::new(
+                ImageTexture::load(base_dir.join(path))
+                    .map_err(|err| format!("cannot load texture `{}`: {}", path, err))?,
+            ),
+            TextureDesc::Noise { scale, seed } => Arc::new(NoiseTexture::new(*scale, *seed)),
+            TextureDesc::Marble {
+                scale,
+                turbulence,
+                seed,
+                dark,
+                light,
+            } => {
+                let mut marble = MarbleTexture::new(*scale, *turbulence, *seed);
+                if let Some(dark) = dark {
+                    marble = marble.with_dark(to_color(dark));
+                }
+                if let Some(light) = light {
+                    marble = marble.with_light(to_color(light));
+                }
+                Arc::new(marble)
+            }
+            TextureDesc::Wood {
+                scale,
+                turbulence,
+                seed,
+                dark,
+                light,
+            } => {
+                let mut wood = WoodTexture::new(*scale, *turbulence, *seed);
+                if let Some(dark) = dark {
+                    wood = wood.with_dark(to_color(dark));
+                }
+                if let Some(light) = light {
+                    wood = wood.with_light(to_color(light));
+                }
+                Arc::new(wood)
+            }
+        })
+    }
+
+    fn default_marble_turbulence() -> f64 {
+        10.0
+    }
+
+    fn default_wood_turbulence() -> f64 {
+        1.0
+    }
+}
+
+/// Placement of an object, applied in order: scale, rotation around x, y and z (in degrees),
+/// translation.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TransformDesc {
+    scale: Option<ScaleDesc>,
+    rotate: Option<[f64; 3]>,
+    translate: Option<[f64; 3]>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScaleDesc {
+    Uniform(f64),
+    Axes([f64; 3]),
+}
+
+impl TransformDesc {
+    fn matrix(&self) -> Result<Matrix4, String> {
+        let scale = match self.scale {
+            None => Vec3::one(),
+            Some(ScaleDesc::Uniform(factor)) => Vec3::new(factor, factor, factor),
+            Some(ScaleDesc::Axes(factors)) => to_point(&factors),
+        };
+        if scale.x() * scale.y() * scale.z() == 0.0 {
+            return Err(String::from("in `transform`: `scale` can't be 0"));
+        }
+        let [x, y, z] = self.rotate.unwrap_or([0.0; 3]);
+
+        Ok(Matrix4::translation(&to_point(&self.translate.unwrap_or([0.0; 3])))
+            * Matrix4::rotation(&Vec3::new(0.0, 0.0, 1.0), z)
+            * Matrix4::rotation(&Vec3::up(), y)
+            * Matrix4::rotation(&Vec3::right(), x)
+            * Matrix4::scaling(&scale))
+    }
+}
+
+/// What the rays escaping the scene see, the sky gradient if the scene has no `[background]`.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum BackgroundDesc {
+    Constant {
+        color: [f64; 3],
+    },
+    Gradient {
+        bottom: [f64; 3],
+        top: [f64; 3],
+    },
+    /// Equirectangular environment map (.hdr, .exr or any LDR format)
+    Map {
+        path: String,
+        #[serde(default = "BackgroundDesc::default_intensity")]
+        intensity: f64,
+        /// Rotation around the vertical axis, in degrees
+        #[serde(default)]
+        rotation: f64,
+    },
+}
+
+impl BackgroundDesc {
+    fn default_intensity() -> f64 {
+        1.0
+    }
+
+    fn build(&self, base_dir: &Path) -> Result<Arc<dyn Environment>, String> {
+        Ok(match self {
+            BackgroundDesc::Constant { color } => Arc::new(ConstantEnvironment::new(to_color(color))),
+            BackgroundDesc::Gradient { bottom, top } => {
+                Arc::new(GradientEnvironment::new(to_color(bottom), to_color(top)))
+            }
+            BackgroundDesc::Map {
+                path,
+                intensity,
+                rotation,
+            } => {
+                let map = EnvironmentMap::load(base_dir.join(path))
+                    .map_err(|err| format!("cannot load environment map `{}`: {}", path, err))?;
+                Arc::new(map.with_intensity(*intensity).with_rotation(*rotation))
+            }
+        })
+    }
+}
+
+fn to_normal(v: &[f64; 3]) -> Result<Vec3, String> {
+    let normal = to_point(v);
+    if normal.near_zero() {
+        return Err(String::from("`normal` can't be zero"));
+    }
+    Ok(normal)
+}
+
+fn to_color(v: &[f64; 3]) -> Color {
+    Color::new(v[0], v[1], v[2])
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere {
+        center: [f64; 3],
+        /// Where the center is at time 1, for a sphere moving during the shutter interval.
+        end_center: Option<[f64; 3]>,
+        radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        normals: Option<[[f64; 3]; 3]>,
+        material: String,
+    },
+    /// Parallelogram with corner `corner` and sides `u` and `v`
+    Quad {
+        corner: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        material: String,
+    },
+    Disk {
+        center: [f64; 3],
+        normal: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    /// Infinite plane
+    Plane {
+        point: [f64; 3],
+        normal: [f64; 3],
+        material: String,
+    },
+    /// Axis-aligned box between two opposite corners
+    #[serde(rename = "box")]
+    Cuboid {
+        min: [f64; 3],
+        max: [f64; 3],
+        material: String,
+    },
+    Obj {
+        path: String,
+        /// Material of the faces that don't pick one from the OBJ's own MTL files.
+        material: Option<String>,
+    },
+}
+
+impl ObjectDesc {
+    fn material(&self) -> Option<&str> {
+        match self {
+            ObjectDesc::Sphere { material, .. }
+            | ObjectDesc::Triangle { material, .. }
+            | ObjectDesc::Quad { material, .. }
+            | ObjectDesc::Disk { material, .. }
+            | ObjectDesc::Plane { material, .. }
+            | ObjectDesc::Cuboid { material, .. } => Some(material),
+            ObjectDesc::Obj { material, .. } => material.as_deref(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMERA: &str = "[camera]\nlookfrom = [0, 0, 1]\nlookat = [0, 0, 0]\n";
+
+    fn error_of(source: &str) -> SceneError {
+        match Scene::parse(source) {
+            Ok(_) => panic!("scene loaded:\n{}", source),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn unknown_material_is_reported_at_its_object() {
+        let source = format!(
+            "{}\n[materials.white]\ntype = \"lambertian\"\nalbedo = [1, 1, 1]\n\n\
+             [[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"white\"\n\n\
+             [[objects]]\ntype = \"sphere\"\ncenter = [0, 2, 0]\nradius = 1\nmaterial = \"black\"\n",
+            CAMERA
+        );
+        let err = error_of(&source);
+        assert_eq!(err.line(), Some(15));
+        assert!(err.to_string().contains("unknown material `black`"), "{}", err);
+    }
+
+    #[test]
+    fn missing_field_is_reported_at_its_object() {
+        let source = format!(
+            "{}\n[materials.white]\ntype = \"lambertian\"\nalbedo = [1, 1, 1]\n\n\
+             [[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nmaterial = \"white\"\n",
+            CAMERA
+        );
+        let err = error_of(&source);
+        assert_eq!(err.line(), Some(9));
+        assert!(err.to_string().contains("radius"), "{}", err);
+    }
+
+    #[test]
+    fn missing_material_field_is_reported_at_the_material() {
+        let source = format!("{}\n[materials.shiny]\ntype = \"metal\"\nalbedo = [1, 1, 1]\n", CAMERA);
+        let err = error_of(&source);
+        assert_eq!(err.line(), Some(5));
+        assert!(err.to_string().contains("fuzz"), "{}", err);
+    }
+
+    #[test]
+    fn camera_values_out_of_range_are_rejected() {
+        for (field, value) in [("image_width", "0"), ("samples", "0"), ("aspect_ratio", "0.0"), ("aspect_ratio", "-1.5")] {
+            let err = error_of(&format!("{}vfov = 40\n{} = {}\n", CAMERA, field, value));
+            assert_eq!(err.line(), Some(5), "{} = {}", field, value);
+            assert!(err.to_string().contains(field), "{}", err);
+        }
+        assert!(Scene::parse(&format!("{}image_width = 1\nsamples = 1\n", CAMERA)).is_ok());
+    }
+}
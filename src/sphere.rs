@@ -1,31 +1,63 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 
+use super::aabb::Aabb;
 use super::hittable::{HitResult, Hittable};
 use super::interval::Interval;
 use super::material::Material;
+use super::onb::Onb;
+use super::pdf::{random_to_sphere, Pdf, SpherePdf};
 use super::ray::Ray;
 use super::vec3::Vec3;
 use super::Point;
 
 pub struct Sphere {
     center: Point,
+    /// How far the center moves from time 0 to time 1.
+    motion: Vec3,
     radius: f64,
     material: Arc<dyn Material>,
+    bbox: Aabb,
 }
 
 impl Sphere {
     pub fn new(center: Point, radius: f64, material: Arc<dyn Material>) -> Self {
+        Sphere::moving(center, center, radius, material)
+    }
+
+    /// Sphere moving in a straight line from `start` at time 0 to `end` at time 1.
+    pub fn moving(start: Point, end: Point, radius: f64, material: Arc<dyn Material>) -> Self {
+        let radius_vec = Vec3::new(radius, radius, radius);
+        let start_box = Aabb::from_points(start - radius_vec, start + radius_vec);
+        let end_box = Aabb::from_points(end - radius_vec, end + radius_vec);
         Sphere {
-            center,
+            center: start,
+            motion: end - start,
             radius,
             material,
+            bbox: Aabb::enclosing(&start_box, &end_box),
         }
     }
+
+    /// Surface coordinates of a point on the unit sphere: `u` goes around the y axis starting
+    /// from -x, `v` goes from the bottom (-y) to the top.
+    fn uv(point: &Point) -> (f64, f64) {
+        let theta = f64::acos(-point.y().clamp(-1.0, 1.0));
+        let phi = f64::atan2(-*point.z(), *point.x()) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    /// Center of the sphere at `time`.
+    #[inline(always)]
+    pub fn center(&self, time: f64) -> Point {
+        self.center + time * self.motion
+    }
 }
 
 impl Hittable for Sphere {
     fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitResult> {
-        let oc = ray.origin() - self.center;
+        let center = self.center(ray.time());
+        let oc = ray.origin() - center;
         let a = ray.direction().len_squared();
         let half_b = Vec3::dot(&oc, &ray.direction());
         let c = oc.len_squared() - self.radius * self.radius;
@@ -46,13 +78,39 @@ impl Hittable for Sphere {
 
         let t = root;
         let location = ray.at(t);
-        let normal = (location - self.center) / self.radius;
-        return Some(HitResult::new(
-            ray,
-            location,
-            normal,
-            Arc::clone(&self.material),
-            t,
-        ));
+        let normal = (location - center) / self.radius;
+        let (u, v) = Sphere::uv(&normal);
+        Some(
+            HitResult::new(ray, location, normal, Arc::clone(&self.material), t).with_uv(u, v),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        if self.hit(&Ray::new(*origin, *direction), Interval::new(0.001, f64::INFINITY)).is_none() {
+            return 0.0;
+        }
+
+        let distance_squared = (self.center - *origin).len_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            // from inside every direction hits the sphere
+            return SpherePdf.value(direction);
+        }
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: &Point) -> Vec3 {
+        let direction = self.center - *origin;
+        let distance_squared = direction.len_squared();
+        if distance_squared <= self.radius * self.radius {
+            return SpherePdf.generate();
+        }
+        Onb::new(&direction).transform(&random_to_sphere(self.radius, distance_squared))
     }
 }
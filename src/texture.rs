@@ -0,0 +1,225 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use image::DynamicImage;
+
+use super::color::Color;
+use super::perlin::Perlin;
+use super::Point;
+
+/// Color varying over a surface, looked up with the (`u`, `v`) surface coordinates of the hit
+/// and its location in space.
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, point: &Point) -> Color;
+}
+
+/// Same color everywhere.
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        SolidColor { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _point: &Point) -> Color {
+        self.color
+    }
+}
+
+/// 3D checkerboard of `scale` sized cubes alternating between two textures, so it wraps any
+/// shape without needing surface coordinates.
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        CheckerTexture {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+
+    pub fn from_colors(scale: f64, even: Color, odd: Color) -> Self {
+        CheckerTexture::new(scale, Arc::new(SolidColor::new(even)), Arc::new(SolidColor::new(odd)))
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, point: &Point) -> Color {
+        let cell = |c: f64| (self.inv_scale * c).floor() as i64;
+        let sum = cell(*point.x()) + cell(*point.y()) + cell(*point.z());
+
+        if sum.rem_euclid(2) == 0 {
+            self.even.value(u, v, point)
+        } else {
+            self.odd.value(u, v, point)
+        }
+    }
+}
+
+/// Image mapped over the surface coordinates, (0, 0) being the bottom left corner of the
+/// image. Coordinates outside 0..1 repeat the image.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl ImageTexture {
+    /// Loads any format the `image` crate can decode. 8 and 16-bit images are taken as sRGB
+    /// encoded and converted to linear, floating point ones (.hdr, .exr) are already linear.
+    pub fn load<P: AsRef<Path>>(path: P) -> image::ImageResult<Self> {
+        let image = image::open(path)?;
+        let linear = matches!(image, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_));
+        let image = image.into_rgb32f();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+
+        let mut data = image.into_raw();
+        if !linear {
+            for texel in data.chunks_exact_mut(3) {
+                let srgb = Color::new(texel[0] as f64, texel[1] as f64, texel[2] as f64);
+                let color = Color::srgb_to_linear(&srgb);
+                texel.copy_from_slice(&[*color.r() as f32, *color.g() as f32, *color.b() as f32]);
+            }
+        }
+
+        Ok(ImageTexture {
+            width,
+            height,
+            data,
+        })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _point: &Point) -> Color {
+        let u = u.rem_euclid(1.0);
+        let v = 1.0 - v.rem_euclid(1.0);
+
+        let x = usize::min((u * self.width as f64) as usize, self.width - 1);
+        let y = usize::min((v * self.height as f64) as usize, self.height - 1);
+        let index = (y * self.width + x) * 3;
+
+        Color::new(
+            self.data[index] as f64,
+            self.data[index + 1] as f64,
+            self.data[index + 2] as f64,
+        )
+    }
+}
+
+/// Octaves of turbulence summed by the procedural textures.
+const TURBULENCE_DEPTH: u32 = 7;
+
+/// Plain Perlin noise in gray levels, `scale` being the frequency of the features.
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64, seed: u64) -> Self {
+        NoiseTexture {
+            noise: Perlin::new(seed),
+            scale,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, point: &Point) -> Color {
+        0.5 * (1.0 + self.noise.noise(&(self.scale * *point))) * Color::white()
+    }
+}
+
+/// Marble: stripes along z (sine waves) bent by turbulence, going from `dark` veins to
+/// `light` stone.
+pub struct MarbleTexture {
+    noise: Perlin,
+    scale: f64,
+    turbulence: f64,
+    dark: Color,
+    light: Color,
+}
+
+impl MarbleTexture {
+    /// `scale` is the frequency of the stripes, `turbulence` how much they get distorted.
+    pub fn new(scale: f64, turbulence: f64, seed: u64) -> Self {
+        MarbleTexture {
+            noise: Perlin::new(seed),
+            scale,
+            turbulence,
+            dark: Color::black(),
+            light: Color::white(),
+        }
+    }
+
+    pub fn with_dark(mut self, dark: Color) -> Self {
+        self.dark = dark;
+        self
+    }
+
+    pub fn with_light(mut self, light: Color) -> Self {
+        self.light = light;
+        self
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, point: &Point) -> Color {
+        let turbulence = self.noise.turbulence(point, TURBULENCE_DEPTH);
+        let t = 0.5 * (1.0 + f64::sin(self.scale * point.z() + self.turbulence * turbulence));
+        (1.0 - t) * self.dark + t * self.light
+    }
+}
+
+/// Wood: concentric growth rings around the y axis, `scale` rings per unit, made irregular
+/// by turbulence.
+pub struct WoodTexture {
+    noise: Perlin,
+    scale: f64,
+    turbulence: f64,
+    dark: Color,
+    light: Color,
+}
+
+impl WoodTexture {
+    pub fn new(scale: f64, turbulence: f64, seed: u64) -> Self {
+        WoodTexture {
+            noise: Perlin::new(seed),
+            scale,
+            turbulence,
+            dark: Color::new(0.36, 0.2, 0.09),
+            light: Color::new(0.72, 0.52, 0.3),
+        }
+    }
+
+    pub fn with_dark(mut self, dark: Color) -> Self {
+        self.dark = dark;
+        self
+    }
+
+    pub fn with_light(mut self, light: Color) -> Self {
+        self.light = light;
+        self
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, point: &Point) -> Color {
+        let radius = f64::sqrt(point.x() * point.x() + point.z() * point.z());
+        let turbulence = self.noise.turbulence(point, TURBULENCE_DEPTH);
+        let rings = (self.scale * radius + self.turbulence * turbulence).fract();
+        // sharp dark edge at the end of every ring, fading back to light wood
+        let t = rings * rings;
+        (1.0 - t) * self.light + t * self.dark
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use super::color::Color;
+use super::interval::Interval;
+
+/// Curve compressing scene radiance into the displayable 0..1 range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapOperator {
+    /// No compression, everything above 1 is clipped.
+    Clamp,
+    /// `c / (1 + c)` per channel, never clips but flattens the highlights.
+    Reinhard,
+    /// Stephen Hill's fit of the ACES reference rendering and sRGB output transforms, a filmic
+    /// curve with a toe and a soft shoulder.
+    Aces,
+}
+
+impl ToneMapOperator {
+    pub const NAMES: [&'static str; 3] = ["clamp", "reinhard", "aces"];
+}
+
+impl FromStr for ToneMapOperator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(ToneMapOperator::Clamp),
+            "reinhard" => Ok(ToneMapOperator::Reinhard),
+            "aces" => Ok(ToneMapOperator::Aces),
+            _ => Err(format!(
+                "unknown tone mapping operator `{}`, expected one of: {}",
+                s,
+                ToneMapOperator::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl Display for ToneMapOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ToneMapOperator::Clamp => "clamp",
+            ToneMapOperator::Reinhard => "reinhard",
+            ToneMapOperator::Aces => "aces",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Post-process turning the linear radiance of the framebuffer into 8-bit sRGB:
+/// exposure, then the tone mapping curve, then the sRGB transfer function.
+#[derive(Debug, Clone, Copy)]
+pub struct ToneMapping {
+    operator: ToneMapOperator,
+    exposure: f64,
+}
+
+impl ToneMapping {
+    /// `exposure` is in stops: every +1 doubles the brightness before the curve is applied.
+    pub fn new(operator: ToneMapOperator, exposure: f64) -> Self {
+        ToneMapping { operator, exposure }
+    }
+
+    #[inline(always)]
+    pub fn operator(&self) -> ToneMapOperator {
+        self.operator
+    }
+
+    #[inline(always)]
+    pub fn exposure(&self) -> f64 {
+        self.exposure
+    }
+
+    /// Display referred linear color, every channel in 0..1.
+    pub fn map(&self, color: &Color) -> Color {
+        let color = f64::exp2(self.exposure) * *color;
+        let display: Interval = Interval::new(0.0, 1.0);
+
+        let mapped = match self.operator {
+            ToneMapOperator::Clamp => color,
+            ToneMapOperator::Reinhard => Color::new(
+                reinhard(*color.r()),
+                reinhard(*color.g()),
+                reinhard(*color.b()),
+            ),
+            ToneMapOperator::Aces => aces(&color),
+        };
+
+        Color::new(
+            display.clamp(*mapped.r()),
+            display.clamp(*mapped.g()),
+            display.clamp(*mapped.b()),
+        )
+    }
+
+    /// Tone maps and sRGB encodes a linear color to 8 bits per channel.
+    pub fn encode(&self, color: &Color) -> [u8; 3] {
+        let color = Color::linear_to_srgb(&self.map(color));
+        [
+            (255f64 * color.r()).round() as u8,
+            (255f64 * color.g()).round() as u8,
+            (255f64 * color.b()).round() as u8,
+        ]
+    }
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping::new(ToneMapOperator::Clamp, 0.0)
+    }
+}
+
+#[inline(always)]
+fn reinhard(value: f64) -> f64 {
+    // negative radiance can only come from numerical noise
+    let value = value.max(0.0);
+    value / (1.0 + value)
+}
+
+fn aces(color: &Color) -> Color {
+    // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+    const INPUT: [[f64; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    // ODT_SAT => XYZ => D60_2_D65 => sRGB
+    const OUTPUT: [[f64; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+
+    let v = multiply(&INPUT, color);
+    let fit = |x: f64| {
+        let a = x * (x + 0.0245786) - 0.000090537;
+        let b = x * (0.983729 * x + 0.4329510) + 0.238081;
+        a / b
+    };
+    multiply(&OUTPUT, &Color::new(fit(*v.r()), fit(*v.g()), fit(*v.b())))
+}
+
+fn multiply(m: &[[f64; 3]; 3], c: &Color) -> Color {
+    Color::new(
+        m[0][0] * c.r() + m[0][1] * c.g() + m[0][2] * c.b(),
+        m[1][0] * c.r() + m[1][1] * c.g() + m[1][2] * c.b(),
+        m[2][0] * c.r() + m[2][1] * c.g() + m[2][2] * c.b(),
+    )
+}
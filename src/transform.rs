@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hittable::{HitResult, Hittable};
+use super::interval::Interval;
+use super::matrix::Matrix4;
+use super::ray::Ray;
+use super::vec3::Vec3;
+use super::Point;
+
+/// Instance of a hittable placed in the scene by an affine transform.
+///
+/// The object itself stays in its own space and is only referenced, so one mesh can be
+/// instanced any number of times at the cost of an `Arc`.
+pub struct Transform {
+    object: Arc<dyn Hittable>,
+    matrix: Matrix4,
+    inverse: Matrix4,
+    /// Inverse transpose of `matrix`, keeps the normals perpendicular to scaled surfaces.
+    normal_matrix: Matrix4,
+    bbox: Aabb,
+}
+
+impl Transform {
+    /// Places `object` with `matrix`.
+    ///
+    /// # Panics
+    ///
+    /// If `matrix` is not invertible.
+    pub fn new(object: Arc<dyn Hittable>, matrix: Matrix4) -> Self {
+        let inverse = matrix
+            .inverse()
+            .expect("the transform matrix must be invertible");
+
+        let object_bbox = object.bounding_box();
+        let mut bbox = Aabb::empty();
+        if object_bbox.is_finite() {
+            for corner in object_bbox.corners() {
+                let p = matrix.transform_point(&corner);
+                bbox = Aabb::enclosing(&bbox, &Aabb::from_points(p, p));
+            }
+        } else {
+            bbox = Aabb::universe();
+        }
+
+        Transform {
+            object,
+            matrix,
+            inverse,
+            normal_matrix: inverse.transpose(),
+            bbox,
+        }
+    }
+
+    #[inline(always)]
+    pub fn matrix(&self) -> &Matrix4 {
+        &self.matrix
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitResult> {
+        // the direction isn't normalized, so `t` is the same in both spaces
+        let local_ray = Ray::with_time(
+            self.inverse.transform_point(&ray.origin()),
+            self.inverse.transform_vector(&ray.direction()),
+            ray.time(),
+        );
+
+        let hit = self.object.hit(&local_ray, interval)?;
+        let location = self.matrix.transform_point(hit.location());
+        let normal = self.normal_matrix.transform_vector(hit.normal()).normalized();
+        Some(hit.transform(location, normal))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    /// Solid angles are only preserved by rotations, translations and uniform scales, so lights
+    /// with any other transform are sampled with a slightly wrong density.
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        self.object.pdf_value(
+            &self.inverse.transform_point(origin),
+            &self.inverse.transform_vector(direction),
+        )
+    }
+
+    fn random(&self, origin: &Point) -> Vec3 {
+        let local = self.object.random(&self.inverse.transform_point(origin));
+        self.matrix.transform_vector(&local)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+
+    #[test]
+    fn stretched_sphere_hit_is_in_world_space() {
+        // unit sphere stretched twice as wide along x, then moved 5 along x
+        let sphere = Arc::new(Sphere::new(Point::zero(), 1.0, Arc::new(Lambertian::new(Color::white()))));
+        let matrix = Matrix4::translation(&Vec3::new(5.0, 0.0, 0.0)) * Matrix4::scaling(&Vec3::new(2.0, 1.0, 1.0));
+        let transform = Transform::new(sphere, matrix);
+
+        let ray = Ray::new(Point::new(6.0, 0.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = transform.hit(&ray, Interval::new(0.001, f64::INFINITY)).unwrap();
+
+        let z = 0.75_f64.sqrt();
+        assert!((*hit.location() - Point::new(6.0, 0.0, z)).len() < 1e-9, "{:?}", hit.location());
+        assert!((hit.t() - (10.0 - z)).abs() < 1e-9);
+        // gradient of x² / 4 + y² + z², not the object space normal moved by the matrix
+        let expected = Vec3::new(0.5, 0.0, 2.0 * z).normalized();
+        assert!((hit.normal().len() - 1.0).abs() < 1e-9);
+        assert!((*hit.normal() - expected).len() < 1e-9, "{:?}", hit.normal());
+    }
+}
@@ -0,0 +1,275 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::bvh::BvhNode;
+use super::hittable::{HitResult, Hittable};
+use super::interval::Interval;
+use super::material::Material;
+use super::pdf::area_to_solid_angle;
+use super::ray::Ray;
+use super::utilities::rand;
+use super::vec3::Vec3;
+use super::Point;
+
+pub struct Triangle {
+    vertices: [Point; 3],
+    normals: Option<[Vec3; 3]>,
+    material: Arc<dyn Material>,
+    bbox: Aabb,
+}
+
+impl Triangle {
+    pub fn new(a: Point, b: Point, c: Point, material: Arc<dyn Material>) -> Self {
+        Triangle {
+            vertices: [a, b, c],
+            normals: None,
+            material,
+            bbox: triangle_bbox(&a, &b, &c),
+        }
+    }
+
+    /// Triangle shaded with the given per-vertex normals, interpolated across the face.
+    pub fn with_normals(
+        a: Point,
+        b: Point,
+        c: Point,
+        normals: [Vec3; 3],
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Triangle {
+            vertices: [a, b, c],
+            normals: Some(normals),
+            material,
+            bbox: triangle_bbox(&a, &b, &c),
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitResult> {
+        let [a, b, c] = &self.vertices;
+        let (t, u, v) = intersect(a, b, c, ray, &interval)?;
+        let normal = shading_normal(a, b, c, self.normals.as_ref(), u, v);
+
+        Some(HitResult::new(ray, ray.at(t), normal, Arc::clone(&self.material), t).with_uv(u, v))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point, direction: &Vec3) -> f64 {
+        let [a, b, c] = &self.vertices;
+        match intersect(a, b, c, &Ray::new(*origin, *direction), &Interval::new(0.001, f64::INFINITY)) {
+            Some((t, _, _)) => {
+                let n = Vec3::cross(&(*b - *a), &(*c - *a));
+                let distance_squared = t * t * direction.len_squared();
+                area_to_solid_angle(0.5 * n.len(), distance_squared, direction, &n.normalized())
+            }
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Point) -> Vec3 {
+        let [a, b, c] = &self.vertices;
+        // folding the far half of the parallelogram back keeps the points uniform
+        let (mut u, mut v) = (rand(), rand());
+        if u + v > 1.0 {
+            (u, v) = (1.0 - u, 1.0 - v);
+        }
+        *a + u * (*b - *a) + v * (*c - *a) - *origin
+    }
+}
+
+/// Indexed triangle mesh.
+///
+/// All the triangles share the same vertex buffers and material, so large meshes only pay for
+/// one copy of every vertex. The triangles are kept in a BVH of their own, so the mesh can be
+/// added to a scene as a single object.
+pub struct TriangleMesh {
+    bvh: BvhNode,
+}
+
+struct MeshData {
+    positions: Vec<Point>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    indices: Vec<[usize; 3]>,
+    material: Arc<dyn Material>,
+}
+
+/// One face of a `TriangleMesh`, pointing back into the shared buffers.
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    face: usize,
+}
+
+impl TriangleMesh {
+    /// Builds a mesh out of `indices`, each entry being the three vertices of a triangle.
+    /// `normals` and `uvs`, if given, must have one entry per position.
+    ///
+    /// Panics if an index is out of bounds or the attributes don't match the positions.
+    pub fn new(
+        positions: Vec<Point>,
+        normals: Option<Vec<Vec3>>,
+        uvs: Option<Vec<(f64, f64)>>,
+        indices: Vec<[usize; 3]>,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        if let Some(normals) = &normals {
+            assert_eq!(
+                normals.len(),
+                positions.len(),
+                "mesh needs exactly one normal per vertex"
+            );
+        }
+        if let Some(uvs) = &uvs {
+            assert_eq!(
+                uvs.len(),
+                positions.len(),
+                "mesh needs exactly one texture coordinate per vertex"
+            );
+        }
+        if let Some(index) = indices.iter().flatten().find(|&&i| i >= positions.len()) {
+            panic!(
+                "mesh index {} out of bounds for {} vertices",
+                index,
+                positions.len()
+            );
+        }
+
+        let face_count = indices.len();
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            indices,
+            material,
+        });
+
+        let triangles: Vec<Arc<dyn Hittable>> = (0..face_count)
+            .map(|face| {
+                Arc::new(MeshTriangle {
+                    mesh: Arc::clone(&mesh),
+                    face,
+                }) as Arc<dyn Hittable>
+            })
+            .collect();
+
+        TriangleMesh {
+            bvh: BvhNode::new(triangles),
+        }
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitResult> {
+        self.bvh.hit(ray, interval)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+impl MeshTriangle {
+    #[inline(always)]
+    fn vertices(&self) -> (&Point, &Point, &Point) {
+        let [i, j, k] = self.mesh.indices[self.face];
+        let positions = &self.mesh.positions;
+        (&positions[i], &positions[j], &positions[k])
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<HitResult> {
+        let (a, b, c) = self.vertices();
+        let (t, u, v) = intersect(a, b, c, ray, &interval)?;
+
+        let normals = self.mesh.normals.as_ref().map(|normals| {
+            let [i, j, k] = self.mesh.indices[self.face];
+            [normals[i], normals[j], normals[k]]
+        });
+        let normal = shading_normal(a, b, c, normals.as_ref(), u, v);
+
+        let hit = HitResult::new(ray, ray.at(t), normal, Arc::clone(&self.mesh.material), t);
+        match &self.mesh.uvs {
+            Some(uvs) => {
+                let [i, j, k] = self.mesh.indices[self.face];
+                let w = 1.0 - u - v;
+                Some(hit.with_uv(
+                    w * uvs[i].0 + u * uvs[j].0 + v * uvs[k].0,
+                    w * uvs[i].1 + u * uvs[j].1 + v * uvs[k].1,
+                ))
+            }
+            None => Some(hit),
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let (a, b, c) = self.vertices();
+        triangle_bbox(a, b, c)
+    }
+}
+
+fn triangle_bbox(a: &Point, b: &Point, c: &Point) -> Aabb {
+    Aabb::enclosing(&Aabb::from_points(*a, *b), &Aabb::from_points(*a, *c))
+}
+
+/// Möller–Trumbore ray/triangle intersection.
+///
+/// Returns the ray parameter `t` and the barycentric coordinates `(u, v)` of the hit point,
+/// weighting `b` and `c` respectively (`a` gets `1 - u - v`).
+fn intersect(
+    a: &Point,
+    b: &Point,
+    c: &Point,
+    ray: &Ray,
+    interval: &Interval,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = *b - *a;
+    let edge2 = *c - *a;
+    let p = Vec3::cross(&ray.direction(), &edge2);
+    let det = Vec3::dot(&edge1, &p);
+
+    // ray parallel to the triangle plane
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let s = ray.origin() - *a;
+    let u = Vec3::dot(&s, &p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = Vec3::cross(&s, &edge1);
+    let v = Vec3::dot(&ray.direction(), &q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = Vec3::dot(&edge2, &q) * inv_det;
+    if !interval.surrounds(t) {
+        return None;
+    }
+
+    Some((t, u, v))
+}
+
+/// Interpolated vertex normal if available, the geometric normal otherwise.
+/// Vertex normals take precedence over the winding order to decide which side is the front.
+fn shading_normal(
+    a: &Point,
+    b: &Point,
+    c: &Point,
+    normals: Option<&[Vec3; 3]>,
+    u: f64,
+    v: f64,
+) -> Vec3 {
+    match normals {
+        None => Vec3::cross(&(*b - *a), &(*c - *a)).normalized(),
+        Some([na, nb, nc]) => ((1.0 - u - v) * *na + u * *nb + v * *nc).normalized(),
+    }
+}
@@ -1,20 +1,55 @@
-const PI: f64 = 3.1415926535897932385;
+use std::f64::consts::PI;
 
 #[inline(always)]
 pub fn deg_to_rad(degrees: f64) -> f64 {
     degrees * PI / 180.0
 }
 
+use std::cell::RefCell;
 use std::time::Duration;
 
 use rand::*;
+use rand_pcg::Pcg64Mcg;
+
+// PCG has a fixed, documented output stream, so seeded sequences are the same on every
+// platform and rand version
+thread_local! {
+    static RNG: RefCell<Pcg64Mcg> = RefCell::new(Pcg64Mcg::from_entropy());
+}
+
+/// Reseeds the random generator of the calling thread, making the following
+/// `rand`/`rand_range` calls on this thread reproducible.
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = Pcg64Mcg::seed_from_u64(seed));
+}
+
+/// Reseeds the generator of the calling thread for one sample of one pixel.
+///
+/// Every sample gets its own stream derived from the render seed, so what a sample computes
+/// doesn't depend on which thread runs it or on what that thread rendered before.
+pub fn seed_sample(seed: u64, pixel: u64, sample: u64) {
+    self::seed(mix(mix(mix(seed) ^ pixel) ^ sample));
+}
+
+/// Picks a seed from the operating system's entropy source.
+pub fn random_seed() -> u64 {
+    rand::random()
+}
+
+/// SplitMix64 finalizer, scrambles nearby inputs into unrelated outputs.
+pub(crate) fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
 
 pub fn rand() -> f64 {
-    random()
+    RNG.with(|rng| rng.borrow_mut().gen())
 }
 
 pub fn rand_range(min: f64, max: f64) -> f64 {
-    thread_rng().gen_range(min..max)
+    RNG.with(|rng| rng.borrow_mut().gen_range(min..max))
 }
 
 pub fn print_duration(duration: Duration) {
@@ -22,4 +57,4 @@ pub fn print_duration(duration: Duration) {
     let minutes = (duration.as_secs() / 60) % 60;
     let hours = duration.as_secs() / 60 / 60;
     println!("{}:{:02}:{:02}", hours, minutes, seconds)
-}
\ No newline at end of file
+}
@@ -88,8 +88,7 @@ impl Vec3 {
     }
 
     pub fn normalized(&self) -> Self {
-        let temp = self.clone() / self.len();
-        temp
+        *self / self.len()
     }
 
     #[inline(always)]
@@ -144,7 +143,7 @@ impl Vec3 {
     pub fn near_zero(&self) -> bool {
         // small number
         let s = 1e-8;
-        *self.x() < s && *self.y() < s && *self.z() < s
+        self.x().abs() < s && self.y().abs() < s && self.z().abs() < s
     }
 
     pub fn reflect(v: &Vec3, normal: &Vec3) -> Vec3 {
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use raytracing_series::camera::Camera;
+use raytracing_series::color::Color;
+use raytracing_series::hittable_list::HittableList;
+use raytracing_series::material::{Dielectric, Lambertian, Metal};
+use raytracing_series::raytracer::{ExecutionMethod, Raytracer, RaytracerOptions};
+use raytracing_series::sphere::Sphere;
+use raytracing_series::Point;
+
+const SAMPLES: u32 = 8;
+
+fn world() -> HittableList {
+    let mut world = HittableList::new();
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, -100.5, -1.0),
+        100.0,
+        Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0))),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(0.0, 0.0, -1.2),
+        0.5,
+        Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5))),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(-1.0, 0.0, -1.0),
+        0.5,
+        Arc::new(Dielectric::new(1.5)),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point::new(1.0, 0.0, -1.0),
+        0.5,
+        Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.3)),
+    )));
+    world
+}
+
+fn camera() -> Camera {
+    let mut camera = Camera::new(
+        1.5,
+        24,
+        SAMPLES,
+        8,
+        60.0,
+        Point::new(0.0, 0.5, 1.0),
+        Point::new(0.0, 0.0, -1.0),
+        2.0,
+        0.5,
+    );
+    camera.set_seed(42);
+    camera
+}
+
+fn render(options: RaytracerOptions) -> Vec<f64> {
+    let mut raytracer = Raytracer::with_camera(camera());
+    raytracer.render(&world(), options);
+    raytracer.framebuffer().data().to_vec()
+}
+
+#[test]
+fn parallel_render_matches_single_threaded() {
+    let single = render(RaytracerOptions::new(ExecutionMethod::Single));
+    let parallel = render(RaytracerOptions::new(ExecutionMethod::Parallel).with_threads(4));
+    assert_eq!(single, parallel);
+}
+
+#[test]
+fn progressive_render_matches_one_shot() {
+    let one_shot = render(RaytracerOptions::new(ExecutionMethod::Single));
+    let progressive = render(RaytracerOptions::new(ExecutionMethod::Single).with_pass_samples(SAMPLES / 4));
+    assert_eq!(one_shot, progressive);
+
+    let parallel = render(
+        RaytracerOptions::new(ExecutionMethod::Parallel)
+            .with_threads(4)
+            .with_pass_samples(3),
+    );
+    assert_eq!(one_shot, parallel);
+}
+
+#[test]
+fn empty_passes_still_finish() {
+    let one_shot = render(RaytracerOptions::new(ExecutionMethod::Single));
+    let progressive = render(RaytracerOptions::new(ExecutionMethod::Single).with_pass_samples(0));
+    assert_eq!(one_shot, progressive);
+}